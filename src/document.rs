@@ -0,0 +1,197 @@
+use std::{fmt, fs::File, io::{self, Read}};
+use crate::*;
+use crate::statement::{statements, strip_comment};
+
+/// One statement of an `ObjDocument`, along with its exact source text.
+/// Comments and blank lines are statements without any tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjStatement{
+    line:usize,
+    raw:String,
+    tokens:Vec<String>
+}
+
+impl ObjStatement{
+    /// A new statement to insert into a document, a line ending is added if
+    /// `text` doesn't have one.
+    pub fn new(text:&str)->Self{
+        let mut raw = text.to_string();
+        if !raw.ends_with('\n'){
+            raw.push('\n');
+        }
+        Self::from_raw(0, raw)
+    }
+
+    fn from_raw(line:usize, raw:String)->Self{
+        let tokens = match statements(raw.as_bytes(), None).next(){
+            Some(Ok(statement)) => statement.tokens().iter().map(|t| t.text.to_string()).collect(),
+            _ => Vec::new()
+        };
+        ObjStatement { line, raw, tokens }
+    }
+
+    /// Line the statement starts on in the source, 0 for inserted ones.
+    pub fn line(&self)->usize{
+        self.line
+    }
+
+    /// The statement's text, including continuation lines, comments and the
+    /// line ending.
+    pub fn raw(&self)->&str{
+        &self.raw
+    }
+
+    pub fn keyword(&self)->Option<&str>{
+        self.tokens.first().map(String::as_str)
+    }
+
+    /// The tokens after the keyword.
+    pub fn args(&self)->&[String]{
+        self.tokens.get(1..).unwrap_or_default()
+    }
+
+    pub fn tokens(&self)->&[String]{
+        &self.tokens
+    }
+
+    /// Replaces the statement's tokens. It's written on one line, keeping
+    /// the indentation, line ending and trailing comment of the original.
+    pub fn set_tokens<S:AsRef<str>>(&mut self, tokens:&[S]){
+        let first = self.raw.split_inclusive('\n').next().unwrap_or_default();
+        let last = self.raw.split_inclusive('\n').next_back().unwrap_or_default();
+        let indent = &first[..first.len() - first.trim_start().len()];
+        let content = last.trim_end_matches(['\r', '\n']);
+        let ending = &last[content.len()..];
+        let comment = content[strip_comment(content).len()..].trim_end();
+
+        self.tokens = tokens.iter().map(|t| t.as_ref().to_string()).collect();
+        let mut raw = format!("{indent}{}", self.tokens.join(" "));
+        if !comment.is_empty(){
+            raw.push(' ');
+            raw.push_str(comment);
+        }
+        raw.push_str(ending);
+        self.raw = raw;
+    }
+
+    /// Replaces the tokens after the keyword.
+    pub fn set_args<S:AsRef<str>>(&mut self, args:&[S]){
+        let keyword = self.keyword().unwrap_or_default().to_string();
+        let tokens:Vec<&str> = std::iter::once(keyword.as_str()).chain(args.iter().map(AsRef::as_ref)).collect();
+        self.set_tokens(&tokens);
+    }
+}
+
+/// An obj kept statement by statement, for making small edits to a file
+/// without disturbing the rest of it. Unmodified documents are written back
+/// out byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ObjDocument{
+    pub statements:Vec<ObjStatement>
+}
+
+impl ObjDocument{
+    pub fn parse(source:&str)->Self{
+        let mut statements = Vec::new();
+        let mut raw = String::new();
+        let mut start = 1;
+        for (line_index, line) in source.split_inclusive('\n').enumerate(){
+            if raw.is_empty(){
+                start = line_index + 1;
+            }
+            raw.push_str(line);
+            // Same continuation rule as the loader, a `\` ending the line
+            // once any comment is removed
+            let content = strip_comment(line.trim_end_matches(['\r', '\n']));
+            let continued = content.trim_end().ends_with('\\') && line.ends_with('\n');
+            if !continued{
+                statements.push(ObjStatement::from_raw(start, std::mem::take(&mut raw)));
+            }
+        }
+        if !raw.is_empty(){
+            statements.push(ObjStatement::from_raw(start, raw));
+        }
+        ObjDocument { statements }
+    }
+
+    pub fn from_file(file_loc:&str)->Result<Self, ObjError>{
+        let file = File::open(file_loc)
+            .map_err(|source| ObjError::Io { loc:Location::new(Some(file_loc), 0, 0), source })?;
+        Self::read(file, Some(file_loc))
+    }
+
+    pub fn from_reader<R:Read>(reader:R)->Result<Self, ObjError>{
+        Self::read(reader, None)
+    }
+
+    fn read<R:Read>(mut reader:R, path:Option<&str>)->Result<Self, ObjError>{
+        let mut source = String::new();
+        reader.read_to_string(&mut source)
+            .map_err(|source| ObjError::Io { loc:Location::new(path, 0, 0), source })?;
+        Ok(Self::parse(&source))
+    }
+
+    /// The statements with the given keyword, eg. every `v`.
+    pub fn find<'a>(&'a mut self, keyword:&'a str)->impl Iterator<Item = &'a mut ObjStatement> + 'a{
+        self.statements.iter_mut().filter(move |s| s.keyword() == Some(keyword))
+    }
+
+    /// Writes the document out, see `Display`.
+    pub fn write<W:io::Write>(&self, mut writer:W)->io::Result<()>{
+        for statement in &self.statements{
+            writer.write_all(statement.raw.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Loads the document as it currently stands.
+    pub fn load(&self, options:&LoadOptions)->Result<ObjLoader, ObjError>{
+        ObjLoader::from_str_with(&self.to_string(), options)
+    }
+}
+
+/// The document's text, unmodified statements exactly as they were read.
+impl fmt::Display for ObjDocument{
+    fn fmt(&self, f:&mut fmt::Formatter<'_>)->fmt::Result{
+        for statement in &self.statements{
+            f.write_str(&statement.raw)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const SRC:&str = "# cube\r\nmtllib a.mtl\n\nv 0 0 0   # origin\nv 1 \\\n  0 0\nv 0 1 0\n\tf 1 2 3";
+
+    #[test]
+    fn round_trip(){
+        let document = ObjDocument::parse(SRC);
+        assert_eq!(document.to_string(), SRC);
+        let mut out = Vec::new();
+        document.write(&mut out).unwrap();
+        assert_eq!(out, SRC.as_bytes());
+
+        let lines:Vec<(usize, Option<&str>)> = document.statements.iter().map(|s| (s.line(), s.keyword())).collect();
+        assert_eq!(lines, [(1, None), (2, Some("mtllib")), (3, None), (4, Some("v")), (5, Some("v")), (7, Some("v")), (8, Some("f"))]);
+        assert_eq!(document.statements[4].args(), ["1", "0", "0"]);
+    }
+
+    #[test]
+    fn edits(){
+        let mut document = ObjDocument::parse(SRC);
+        for v in document.find("v"){
+            let args:Vec<String> = v.args().iter().map(|a| format!("{}", a.parse::<f64>().unwrap() * 2.)).collect();
+            v.set_args(&args);
+        }
+        document.statements[6].set_tokens(&["f", "3", "2", "1"]);
+        document.statements.insert(3, ObjStatement::new("o tri"));
+        assert_eq!(document.to_string(), "# cube\r\nmtllib a.mtl\n\no tri\nv 0 0 0 # origin\nv 2 0 0\nv 0 2 0\n\tf 3 2 1");
+
+        let loader = document.load(&LoadOptions { load_materials:false, ..Default::default() }).unwrap();
+        assert_eq!(loader.indices.len(), 3);
+        assert_eq!(loader.vert_data[..3], [0., 2., 0.]);
+    }
+}
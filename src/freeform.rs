@@ -0,0 +1,668 @@
+use std::{iter, str::FromStr};
+use crate::*;
+use crate::math::*;
+use crate::obj_loader::parse_index;
+use crate::statement::Token;
+
+/// Most steps a curve, or a surface in either direction, is split into.
+const MAX_SEGMENTS:usize = 256;
+/// Steps per knot span used to sample trimming curves.
+const TRIM_STEPS:usize = 16;
+
+/// The basis set by `cstype`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CurveType{
+    /// `bmatrix`, defined by `bmat` and `step`.
+    BasisMatrix,
+    #[default]
+    Bezier,
+    BSpline,
+    Cardinal,
+    Taylor,
+    /// A type the loader doesn't know, never tessellated.
+    Unsupported
+}
+
+/// The free-form attributes in effect when an element was defined. Arrays
+/// hold the u direction first and v second, curves only use u.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FreeformType{
+    pub curve_type:CurveType,
+    /// `cstype rat ...`, control points are weighted by the `w` of their `v`,
+    /// or their `vp` for trimming curves.
+    pub rational:bool,
+    /// `deg`
+    pub degree:[usize;2],
+    /// `bmat`
+    pub basis_matrix:[Vec<f64>;2],
+    /// `step`
+    pub step:[f64;2]
+}
+
+/// One control point of a surface, `v/vt/vn` resolved to 0-based indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlPoint{
+    pub position:usize,
+    pub tex_coord:Option<usize>,
+    pub normal:Option<usize>
+}
+
+/// A `curv` in space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve{
+    pub line:usize,
+    pub freeform_type:FreeformType,
+    /// The part `u0 u1` of the curve that is drawn.
+    pub range:[f64;2],
+    /// Indices into the obj's positions.
+    pub control_points:Vec<usize>,
+    /// `parm u`, knots for b-splines or segment boundaries for bezier.
+    pub parameters:Vec<f64>,
+    /// `sp`, indices into `FreeformGeometry::parameter_vertices`.
+    pub special_points:Vec<usize>,
+    /// Column of the `curv` keyword on `line`.
+    pub column:usize
+}
+
+/// A `curv2` in a surface's parameter space, used for trimming.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve2{
+    pub line:usize,
+    pub freeform_type:FreeformType,
+    /// Indices into `FreeformGeometry::parameter_vertices`.
+    pub control_points:Vec<usize>,
+    /// `parm u`
+    pub parameters:Vec<f64>
+}
+
+/// One piece of a `trim`, `hole` or `scrv` loop, the part `range` of a
+/// `curv2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurveRef{
+    pub range:[f64;2],
+    /// Index into `FreeformGeometry::curves2`.
+    pub curve:usize
+}
+
+/// A `surf`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Surface{
+    pub line:usize,
+    pub freeform_type:FreeformType,
+    /// `s0 s1` and `t0 t1`, the part of the surface that is drawn.
+    pub range:[[f64;2];2],
+    /// Listed with u varying fastest.
+    pub control_points:Vec<ControlPoint>,
+    /// `parm u` and `parm v`
+    pub parameters:[Vec<f64>;2],
+    /// `trim` loops, the outer boundaries of the surface.
+    pub trims:Vec<Vec<CurveRef>>,
+    /// `hole` loops cut out of the surface.
+    pub holes:Vec<Vec<CurveRef>>,
+    /// `scrv`, curves the tessellation should follow.
+    pub special_curves:Vec<Vec<CurveRef>>,
+    /// `sp`, indices into `FreeformGeometry::parameter_vertices`.
+    pub special_points:Vec<usize>,
+    /// Column of the `surf` keyword on `line`.
+    pub column:usize,
+    /// Index into `ObjData::material_names` of the `usemtl` in effect.
+    pub material:Option<usize>,
+    /// Index into `ObjData::objects` of the `o` in effect.
+    pub object:Option<usize>,
+    /// Index into `ObjData::groups` of the `g` in effect.
+    pub group:Option<usize>,
+    /// The `s` group in effect, 0 when smoothing is off.
+    pub smoothing:u32
+}
+
+/// Every free-form element of an obj.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FreeformGeometry{
+    /// `vp u [v [w]]`, with `v` defaulting to 0 and `w` to 1.
+    pub parameter_vertices:Vec<[f64;3]>,
+    pub curves:Vec<Curve>,
+    pub curves2:Vec<Curve2>,
+    pub surfaces:Vec<Surface>
+}
+
+/// The element body statements (`parm`, `trim`, ...) apply to, until `end`.
+#[derive(Debug, Clone, Copy)]
+enum Element{
+    Curve(usize),
+    Curve2(usize),
+    Surface(usize)
+}
+
+/// Keeps track of the free-form state while an obj is parsed.
+#[derive(Debug, Default)]
+pub(crate) struct FreeformParser{
+    freeform_type:FreeformType,
+    element:Option<Element>,
+    pub geometry:FreeformGeometry
+}
+
+impl FreeformParser{
+    /// Handles a free-form statement, returns `false` if it isn't one. `counts`
+    /// are how many positions, tex coords and normals are defined so far.
+    /// Values it doesn't support give `ObjError::Unsupported`, the parser can
+    /// carry on after them if the caller allows it.
+    pub fn statement(&mut self, tokens:&[Token], path:Option<&str>, counts:[usize;3])->Result<bool, ObjError>{
+        let keyword = tokens[0];
+        let args = &tokens[1..];
+        let too_few = |expected:usize| ObjError::ComponentCount {
+            loc:keyword.location(path),
+            keyword:keyword.text.to_string(),
+            expected,
+            found:args.len()
+        };
+        let unsupported = |token:&Token| ObjError::Unsupported {
+            loc:token.location(path),
+            keyword:format!("{} {}", keyword.text, token.text)
+        };
+        let direction = |token:Option<&Token>| match token.map(|t| t.text){
+            Some("u") => Ok(0),
+            Some("v") => Ok(1),
+            Some(_) => Err(unsupported(&args[0])),
+            None => Err(too_few(1))
+        };
+        let vp_count = self.geometry.parameter_vertices.len();
+
+        match keyword.text{
+            "vp" => {
+                let values = floats(args, path)?;
+                let Some(&u) = values.first() else { return Err(too_few(1)) };
+                let v = values.get(1).copied().unwrap_or(0.);
+                let w = values.get(2).copied().unwrap_or(1.);
+                self.geometry.parameter_vertices.push([u, v, w]);
+            }
+            // FREE-FORM ATTRIBUTES
+            "cstype" => {
+                let (rational, name) = match args{
+                    [] => return Err(too_few(1)),
+                    [rat, name, ..] if rat.text == "rat" => (true, name),
+                    [name, ..] => (false, name)
+                };
+                self.freeform_type.curve_type = match name.text{
+                    "bmatrix" => CurveType::BasisMatrix,
+                    "bezier" => CurveType::Bezier,
+                    "bspline" => CurveType::BSpline,
+                    "cardinal" => CurveType::Cardinal,
+                    "taylor" => CurveType::Taylor,
+                    _ => CurveType::Unsupported
+                };
+                self.freeform_type.rational = rational;
+                if self.freeform_type.curve_type == CurveType::Unsupported{
+                    return Err(unsupported(name));
+                }
+            }
+            "deg" => {
+                if args.is_empty(){
+                    return Err(too_few(1));
+                }
+                for (degree, token) in self.freeform_type.degree.iter_mut().zip(args){
+                    *degree = usize::from_str(token.text)
+                        .map_err(|_| ObjError::BadIndex { loc:token.location(path), token:token.text.to_string() })?;
+                }
+            }
+            "bmat" => {
+                let dir = direction(args.first())?;
+                self.freeform_type.basis_matrix[dir] = floats(&args[1..], path)?;
+            }
+            "step" => {
+                let values = floats(args, path)?;
+                if values.is_empty(){
+                    return Err(too_few(1));
+                }
+                for (step, value) in self.freeform_type.step.iter_mut().zip(values){
+                    *step = value;
+                }
+            }
+            // ELEMENTS
+            "curv" => {
+                if args.len() < 4{
+                    return Err(too_few(4));
+                }
+                let range = floats(&args[..2], path)?;
+                let control_points = args[2..].iter()
+                    .map(|token| checked_index(token, token.text, Slot::Position, counts[0], path))
+                    .collect::<Result<_, _>>()?;
+                self.geometry.curves.push(Curve {
+                    line:keyword.line,
+                    freeform_type:self.freeform_type.clone(),
+                    range:[range[0], range[1]],
+                    control_points,
+                    parameters:Vec::new(),
+                    special_points:Vec::new(),
+                    column:keyword.column
+                });
+                self.element = Some(Element::Curve(self.geometry.curves.len()-1));
+            }
+            "curv2" => {
+                if args.len() < 2{
+                    return Err(too_few(2));
+                }
+                let control_points = args.iter()
+                    .map(|token| checked_index(token, token.text, Slot::ParameterVertex, vp_count, path))
+                    .collect::<Result<_, _>>()?;
+                self.geometry.curves2.push(Curve2 {
+                    line:keyword.line,
+                    freeform_type:self.freeform_type.clone(),
+                    control_points,
+                    parameters:Vec::new()
+                });
+                self.element = Some(Element::Curve2(self.geometry.curves2.len()-1));
+            }
+            "surf" => {
+                if args.len() < 5{
+                    return Err(too_few(5));
+                }
+                let range = floats(&args[..4], path)?;
+                let mut control_points = Vec::new();
+                for token in &args[4..]{
+                    let parts:Vec<&str> = token.text.split('/').collect();
+                    let slot = |i:usize, slot:Slot|->Result<Option<usize>, ObjError>{
+                        match parts.get(i){
+                            Some(part) if !part.is_empty() => checked_index(token, part, slot, counts[i], path).map(Some),
+                            _ => Ok(None)
+                        }
+                    };
+                    control_points.push(ControlPoint {
+                        position:checked_index(token, parts[0], Slot::Position, counts[0], path)?,
+                        tex_coord:slot(1, Slot::TexCoord)?,
+                        normal:slot(2, Slot::Normal)?
+                    });
+                }
+                self.geometry.surfaces.push(Surface {
+                    line:keyword.line,
+                    freeform_type:self.freeform_type.clone(),
+                    range:[[range[0], range[1]], [range[2], range[3]]],
+                    control_points,
+                    parameters:[Vec::new(), Vec::new()],
+                    trims:Vec::new(),
+                    holes:Vec::new(),
+                    special_curves:Vec::new(),
+                    special_points:Vec::new(),
+                    column:keyword.column,
+                    material:None,
+                    object:None,
+                    group:None,
+                    smoothing:0
+                });
+                self.element = Some(Element::Surface(self.geometry.surfaces.len()-1));
+            }
+            // BODY STATEMENTS, anything outside an element is left unsupported
+            "parm" if self.element.is_some() => {
+                let dir = direction(args.first())?;
+                let values = floats(&args[1..], path)?;
+                match self.element{
+                    Some(Element::Curve(i)) => self.geometry.curves[i].parameters = values,
+                    Some(Element::Curve2(i)) => self.geometry.curves2[i].parameters = values,
+                    Some(Element::Surface(i)) => self.geometry.surfaces[i].parameters[dir] = values,
+                    None => {}
+                }
+            }
+            "trim" | "hole" | "scrv" if matches!(self.element, Some(Element::Surface(_))) => {
+                if args.len() < 3{
+                    return Err(too_few(3));
+                }
+                let mut curves = Vec::new();
+                for piece in args.chunks(3){
+                    let [start, end, curve] = piece else { return Err(too_few(args.len().div_ceil(3) * 3)) };
+                    let range = floats(&[*start, *end], path)?;
+                    let curve = checked_index(curve, curve.text, Slot::Curve2, self.geometry.curves2.len(), path)?;
+                    curves.push(CurveRef { range:[range[0], range[1]], curve });
+                }
+                let Some(Element::Surface(i)) = self.element else { return Ok(true) };
+                let surface = &mut self.geometry.surfaces[i];
+                match keyword.text{
+                    "trim" => surface.trims.push(curves),
+                    "hole" => surface.holes.push(curves),
+                    _ => surface.special_curves.push(curves)
+                }
+            }
+            "sp" if self.element.is_some() => {
+                let points:Vec<usize> = args.iter()
+                    .map(|token| checked_index(token, token.text, Slot::ParameterVertex, vp_count, path))
+                    .collect::<Result<_, _>>()?;
+                match self.element{
+                    Some(Element::Curve(i)) => self.geometry.curves[i].special_points.extend(points),
+                    Some(Element::Surface(i)) => self.geometry.surfaces[i].special_points.extend(points),
+                    _ => {}
+                }
+            }
+            "end" if self.element.is_some() => self.element = None,
+            _ => return Ok(false)
+        }
+        Ok(true)
+    }
+}
+
+fn floats(tokens:&[Token], path:Option<&str>)->Result<Vec<f64>, ObjError>{
+    tokens.iter().map(|token|{
+        f64::from_str(token.text).map_err(|_| ObjError::BadFloat { loc:token.location(path), token:token.text.to_string() })
+    }).collect()
+}
+
+/// Like `parse_index`, but also rejects indices past the elements defined so
+/// far, as free-form elements always follow the data they use.
+fn checked_index(token:&Token, part:&str, slot:Slot, count:usize, path:Option<&str>)->Result<usize, ObjError>{
+    let index = parse_index(token, part, slot, count, path)?;
+    if index >= count{
+        return Err(ObjError::OutOfRange { loc:token.location(path), slot, index:index as i64 + 1, len:count });
+    }
+    Ok(index)
+}
+
+// TESSELLATION
+
+/// One direction of an element as a b-spline, bezier curves being b-splines
+/// whose inner knots are repeated `degree` times.
+struct Basis{
+    degree:usize,
+    knots:Vec<f64>,
+    /// Number of control points it takes.
+    count:usize
+}
+
+impl Basis{
+    /// `None` for types other than bezier and b-spline, parameters that
+    /// don't make a valid knot vector, or a degree too high for the
+    /// `control_points` there are.
+    fn new(freeform_type:&FreeformType, dir:usize, parameters:&[f64], control_points:usize)->Option<Self>{
+        // The degree comes straight from the file, so is checked before any
+        // arithmetic. Below the control point count it can't overflow.
+        let degree = freeform_type.degree[dir];
+        if degree == 0 || degree >= control_points{
+            return None;
+        }
+        let knots = match freeform_type.curve_type{
+            CurveType::BSpline => parameters.to_vec(),
+            CurveType::Bezier => {
+                let [first, inner @ .., last] = parameters else { return None };
+                // More knots than the control points could use is never valid
+                let len = inner.len().checked_add(1)?.checked_mul(degree)?.checked_add(degree+2)?;
+                if len > control_points + degree + 1{
+                    return None;
+                }
+                iter::repeat_n(*first, degree+1)
+                    .chain(inner.iter().flat_map(|p| iter::repeat_n(*p, degree)))
+                    .chain(iter::repeat_n(*last, degree+1))
+                    .collect()
+            }
+            _ => return None
+        };
+        let count = knots.len().checked_sub(degree+1)?;
+        let ascending = knots.windows(2).all(|w| w[0] <= w[1]);
+        (count > degree && ascending).then_some(Basis { degree, knots, count })
+    }
+
+    /// Evaluates the spline at `t` with de Boor's algorithm, `points` can
+    /// have any number of components.
+    fn eval(&self, points:&[Vec<f64>], t:f64)->Vec<f64>{
+        let p = self.degree;
+        let t = t.clamp(self.knots[p], self.knots[self.count]);
+        let mut k = p;
+        while k < self.count-1 && t >= self.knots[k+1]{
+            k += 1;
+        }
+        let mut d:Vec<Vec<f64>> = points[k-p..=k].to_vec();
+        for r in 1..=p{
+            for j in (r..=p).rev(){
+                let i = j + k - p;
+                let span = self.knots[j+1+k-r] - self.knots[i];
+                let alpha = if span != 0. { (t - self.knots[i]) / span } else { 0. };
+                let (before, after) = d.split_at_mut(j);
+                for (c, prev) in after[0].iter_mut().zip(&before[j-1]){
+                    *c = (1. - alpha) * prev + alpha * *c;
+                }
+            }
+        }
+        d.swap_remove(p)
+    }
+
+    /// Number of non-empty knot spans that overlap `range`.
+    fn spans(&self, range:[f64;2])->usize{
+        let (lo, hi) = (range[0].min(range[1]), range[0].max(range[1]));
+        self.knots.windows(2).filter(|w| w[0] < w[1] && w[1] > lo && w[0] < hi).count()
+    }
+}
+
+/// Scales a point by its weight and appends the weight.
+fn homogeneous(point:&[f64], weight:f64)->Vec<f64>{
+    point.iter().map(|c| c * weight).chain(iter::once(weight)).collect()
+}
+
+/// Divides a homogeneous point back through by its weight.
+fn project(mut point:Vec<f64>)->Vec<f64>{
+    let w = point.pop().unwrap_or(1.);
+    if w != 0.{
+        point.iter_mut().for_each(|c| *c /= w);
+    }
+    point
+}
+
+fn xyz(point:&[f64])->V3{
+    [point[0], point[1], point[2]]
+}
+
+fn lerp(range:[f64;2], i:usize, n:usize)->f64{
+    range[0] + (range[1] - range[0]) * i as f64 / n as f64
+}
+
+/// How many equal steps `range` needs for the middle of every step to be
+/// within `tolerance` of its chord, on each of the curves `eval` samples.
+fn segment_count(eval:&dyn Fn(f64)->Vec<V3>, range:[f64;2], min:usize, tolerance:f64)->usize{
+    let mut n = min.clamp(1, MAX_SEGMENTS);
+    while n < MAX_SEGMENTS{
+        let fits = (0..n).all(|i|{
+            let (a, b) = (lerp(range, i, n), lerp(range, i+1, n));
+            let (start, end, mid) = (eval(a), eval(b), eval((a + b) / 2.));
+            start.iter().zip(&end).zip(&mid)
+                .all(|((s, e), m)| length(sub(*m, scale(add(*s, *e), 0.5))) <= tolerance)
+        });
+        if fits{
+            break;
+        }
+        n *= 2;
+    }
+    n.min(MAX_SEGMENTS)
+}
+
+/// Samples a curve into a polyline, `None` if it can't be tessellated.
+/// `points` are the control point positions.
+pub(crate) fn tessellate_curve(curve:&Curve, points:&[Vec<f64>], weights:&[f64], tolerance:f64)->Option<Vec<V3>>{
+    let basis = Basis::new(&curve.freeform_type, 0, &curve.parameters, points.len())?;
+    if basis.count != points.len(){
+        return None;
+    }
+    let rational = curve.freeform_type.rational;
+    let points:Vec<Vec<f64>> = points.iter().zip(weights)
+        .map(|(p, w)| homogeneous(p, if rational { *w } else { 1. }))
+        .collect();
+    let eval = |t:f64| xyz(&project(basis.eval(&points, t)));
+    let n = segment_count(&|t| vec![eval(t)], curve.range, basis.spans(curve.range), tolerance);
+    Some((0..=n).map(|i| eval(lerp(curve.range, i, n))).collect())
+}
+
+/// A tessellated surface.
+pub(crate) struct SurfaceMesh{
+    /// Samples on a grid with u varying fastest, with the same components as
+    /// the control points.
+    pub points:Vec<Vec<f64>>,
+    /// Normals worked out from the grid.
+    pub normals:Vec<V3>,
+    pub triangles:Vec<[usize;3]>
+}
+
+/// Tessellates a surface into a grid of triangles, `None` if it can't be.
+/// `points` are the control points, positions first followed by any other
+/// components to interpolate. Trimming is approximate: triangles are kept
+/// or dropped whole depending on where their centre falls.
+pub(crate) fn tessellate_surface(surface:&Surface, points:&[Vec<f64>], weights:&[f64], geometry:&FreeformGeometry, tolerance:f64)->Option<SurfaceMesh>{
+    let ftype = &surface.freeform_type;
+    let basis_u = Basis::new(ftype, 0, &surface.parameters[0], points.len())?;
+    let basis_v = Basis::new(ftype, 1, &surface.parameters[1], points.len())?;
+    if basis_u.count * basis_v.count != points.len(){
+        return None;
+    }
+    let points:Vec<Vec<f64>> = points.iter().zip(weights)
+        .map(|(p, w)| homogeneous(p, if ftype.rational { *w } else { 1. }))
+        .collect();
+    let rows:Vec<&[Vec<f64>]> = points.chunks(basis_u.count).collect();
+    // Evaluates every row along u, giving the control points of the
+    // isoparametric curve at `u`
+    let column = |u:f64|->Vec<Vec<f64>>{ rows.iter().map(|row| basis_u.eval(row, u)).collect() };
+    let eval = |u:f64, v:f64| project(basis_v.eval(&column(u), v));
+
+    // Step counts are checked along a few isoparametric curves
+    let [range_u, range_v] = surface.range;
+    let probes = 4;
+    let n_u = segment_count(&|u| (0..=probes).map(|i| xyz(&eval(u, lerp(range_v, i, probes)))).collect(),
+        range_u, basis_u.spans(range_u), tolerance);
+    let n_v = segment_count(&|v| (0..=probes).map(|i| xyz(&eval(lerp(range_u, i, probes), v))).collect(),
+        range_v, basis_v.spans(range_v), tolerance);
+
+    let columns:Vec<Vec<Vec<f64>>> = (0..=n_u).map(|i| column(lerp(range_u, i, n_u))).collect();
+    let mut grid = Vec::with_capacity((n_u+1) * (n_v+1));
+    for j in 0..=n_v{
+        let v = lerp(range_v, j, n_v);
+        grid.extend(columns.iter().map(|column| project(basis_v.eval(column, v))));
+    }
+
+    let at = |i:usize, j:usize| j * (n_u+1) + i;
+    let mut normals = Vec::with_capacity(grid.len());
+    for j in 0..=n_v{
+        for i in 0..=n_u{
+            let du = sub(xyz(&grid[at((i+1).min(n_u), j)]), xyz(&grid[at(i.saturating_sub(1), j)]));
+            let dv = sub(xyz(&grid[at(i, (j+1).min(n_v))]), xyz(&grid[at(i, j.saturating_sub(1))]));
+            normals.push(normalize(cross(du, dv)));
+        }
+    }
+
+    let loops = |loops:&[Vec<CurveRef>]| loops.iter().map(|l| trim_loop(l, geometry)).collect::<Option<Vec<_>>>();
+    let trims = loops(&surface.trims)?;
+    let holes = loops(&surface.holes)?;
+    let mut triangles = Vec::new();
+    for j in 0..n_v{
+        for i in 0..n_u{
+            // Wound counter-clockwise in (u, v), facing along du x dv
+            let quad = [(i, j), (i+1, j), (i+1, j+1), (i, j+1)];
+            for [a, b, c] in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]]{
+                let centre = [
+                    lerp(range_u, a.0 + b.0 + c.0, 3 * n_u),
+                    lerp(range_v, a.1 + b.1 + c.1, 3 * n_v)
+                ];
+                if (trims.is_empty() || inside(&trims, centre)) && !inside(&holes, centre){
+                    triangles.push([at(a.0, a.1), at(b.0, b.1), at(c.0, c.1)]);
+                }
+            }
+        }
+    }
+    Some(SurfaceMesh { points:grid, normals, triangles })
+}
+
+/// Samples a `trim` or `hole` loop into a polygon in parameter space.
+fn trim_loop(pieces:&[CurveRef], geometry:&FreeformGeometry)->Option<Vec<[f64;2]>>{
+    let mut polygon = Vec::new();
+    for piece in pieces{
+        let curve = geometry.curves2.get(piece.curve)?;
+        let basis = Basis::new(&curve.freeform_type, 0, &curve.parameters, curve.control_points.len())?;
+        if basis.count != curve.control_points.len(){
+            return None;
+        }
+        let points:Vec<Vec<f64>> = curve.control_points.iter().map(|&i|{
+            let [u, v, w] = geometry.parameter_vertices[i];
+            homogeneous(&[u, v], if curve.freeform_type.rational { w } else { 1. })
+        }).collect();
+        // Each piece ends where the next starts, so its last point is left out
+        let n = basis.spans(piece.range).max(1) * TRIM_STEPS;
+        polygon.extend((0..n).map(|i|{
+            let p = project(basis.eval(&points, lerp(piece.range, i, n)));
+            [p[0], p[1]]
+        }));
+    }
+    Some(polygon)
+}
+
+/// Even-odd test of `p` against a set of polygons.
+fn inside(polygons:&[Vec<[f64;2]>], p:[f64;2])->bool{
+    let mut inside = false;
+    for polygon in polygons{
+        for (i, a) in polygon.iter().enumerate(){
+            let b = polygon[(i+1) % polygon.len()];
+            if (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]){
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn tessellated_line(src:&str)->Vec<V3>{
+        let options = LoadOptions { tessellate_freeform:Some(1e-4), ..Default::default() };
+        let loader = ObjLoader::from_str_with(src, &options).unwrap();
+        let info = loader.vertex_info();
+        loader.line_indices.iter().map(|i|{
+            let start = *i as usize * info.stride;
+            [0, 1, 2].map(|c| loader.vert_data_f64()[start + c])
+        }).collect()
+    }
+
+    #[test]
+    fn rational_quarter_circle(){
+        let src = "v 1 0 0 1\nv 1 1 0 0.7071067811865476\nv 0 1 0 1\ncstype rat bezier\ndeg 2\ncurv 0 1 1 2 3\nparm u 0 1\nend\n";
+        let points = tessellated_line(src);
+        assert!(points.len() > 4);
+        assert_eq!(points[0], [1., 0., 0.]);
+        assert!(points.iter().all(|p| (length(*p) - 1.).abs() < 1e-9));
+    }
+
+    #[test]
+    fn bspline_evaluation(){
+        // Clamped cubic with evenly spaced control points on a line, the
+        // curve stays on the line and is symmetric about the middle knot
+        let src = "v 0 0 0\nv 1 0 0\nv 2 0 0\nv 3 0 0\nv 4 0 0\ncstype bspline\ndeg 3\ncurv 0 2 1 2 3 4 5\nparm u 0 0 0 0 1 2 2 2 2\nend\n";
+        let mut points = tessellated_line(src);
+        points.dedup();
+        assert_eq!(points.first(), Some(&[0., 0., 0.]));
+        assert_eq!(points.last(), Some(&[4., 0., 0.]));
+        assert!(points.contains(&[2., 0., 0.]));
+        assert!(points.windows(2).all(|w| w[1][0] > w[0][0] && w[1][1] == 0. && w[1][2] == 0.));
+
+        let basis = Basis::new(&FreeformType { curve_type:CurveType::BSpline, degree:[3, 0], ..Default::default() }, 0, &[0., 0., 0., 0., 1., 2., 2., 2., 2.], 5).unwrap();
+        assert_eq!(basis.count, 5);
+    }
+
+    #[test]
+    fn degree_too_high(){
+        for degree in ["3", "1000000000", "18446744073709551615"]{
+            for curve_type in ["bezier", "bspline"]{
+                let src = format!("v 0 0 0\nv 1 0 0\nv 2 0 0\ncstype {curve_type}\ndeg {degree}\ncurv 0 1 1 2 3\nparm u 0 0.5 1\nend\n");
+                let options = LoadOptions { tessellate_freeform:Some(0.01), ..Default::default() };
+                let loader = ObjLoader::from_str_with(&src, &options).unwrap();
+                assert!(loader.line_indices.is_empty());
+                assert!(matches!(loader.warnings(), [Warning::Untessellated { .. }]));
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_curve_type(){
+        let src = "v 0 0 0\nv 1 0 0\ncstype foo\ndeg 1\ncurv 0 1 1 2\nparm u 0 1\nend\nbmat w 1 0\n";
+        let options = LoadOptions { tessellate_freeform:Some(0.01), ..Default::default() };
+        let loader = ObjLoader::from_str_with(src, &options).unwrap();
+        let keywords:Vec<String> = loader.warnings().iter().map(|w| match w{
+            Warning::Unsupported { keyword, .. } | Warning::Untessellated { keyword, .. } => keyword.clone(),
+            other => panic!("{other}")
+        }).collect();
+        assert_eq!(keywords, ["cstype foo", "bmat w", "curv"]);
+        assert_eq!(loader.freeform().curves[0].freeform_type.curve_type, CurveType::Unsupported);
+
+        let options = LoadOptions { strict:true, ..options };
+        assert!(matches!(ObjLoader::from_str_with(src, &options), Err(ObjError::Unsupported { .. })));
+    }
+}
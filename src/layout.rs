@@ -0,0 +1,331 @@
+use crate::obj_loader::ObjObject;
+
+/// A per-vertex attribute the loader can output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attribute{
+    Position,
+    /// Tex coord `u v`.
+    TexCoord,
+    /// Tex coord `u v w`, for 3d textures.
+    TexCoord3,
+    Normal,
+    /// Tangent `xyz` with the bitangent sign in `w`.
+    Tangent,
+    /// RGBA vertex color, from `v x y z r g b [a]`.
+    Color
+}
+
+impl Attribute{
+    /// Number of components the attribute has.
+    pub fn components(self)->usize{
+        match self{
+            Attribute::Position | Attribute::TexCoord3 | Attribute::Normal => 3,
+            Attribute::TexCoord => 2,
+            Attribute::Tangent | Attribute::Color => 4
+        }
+    }
+}
+
+/// How each component of an attribute is stored. Normalized formats clamp
+/// to `[0, 1]` (unorm) or `[-1, 1]` (snorm) before scaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format{
+    F32,
+    F64,
+    F16,
+    Unorm8,
+    Snorm8,
+    Unorm16,
+    Snorm16
+}
+
+impl Format{
+    /// Size of one component in bytes.
+    pub fn size(self)->usize{
+        match self{
+            Format::F64 => 8,
+            Format::F32 => 4,
+            Format::F16 | Format::Unorm16 | Format::Snorm16 => 2,
+            Format::Unorm8 | Format::Snorm8 => 1
+        }
+    }
+
+    fn write(self, value:f64, out:&mut Vec<u8>){
+        match self{
+            Format::F64 => out.extend_from_slice(&value.to_le_bytes()),
+            Format::F32 => out.extend_from_slice(&(value as f32).to_le_bytes()),
+            Format::F16 => out.extend_from_slice(&f32_to_f16(value as f32).to_le_bytes()),
+            Format::Unorm8 => out.push((value.clamp(0., 1.) * 255.).round() as u8),
+            Format::Snorm8 => out.push(((value.clamp(-1., 1.) * 127.).round() as i8) as u8),
+            Format::Unorm16 => out.extend_from_slice(&((value.clamp(0., 1.) * 65535.).round() as u16).to_le_bytes()),
+            Format::Snorm16 => out.extend_from_slice(&((value.clamp(-1., 1.) * 32767.).round() as i16).to_le_bytes())
+        }
+    }
+}
+
+/// Whether an attribute is written when the obj has no data for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Presence{
+    /// Leave the attribute out of the layout.
+    #[default]
+    IfAvailable,
+    /// Keep it, filled with zeros.
+    Always
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeDesc{
+    pub attribute:Attribute,
+    pub format:Format,
+    pub presence:Presence
+}
+
+impl AttributeDesc{
+    pub fn new(attribute:Attribute, format:Format)->Self{
+        AttributeDesc { attribute, format, presence:Presence::IfAvailable }
+    }
+}
+
+/// Whether attributes share one buffer or get one each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Streams{
+    #[default]
+    Interleaved,
+    Separate
+}
+
+/// Describes the vertex buffers `ObjLoader::pack` should produce, attributes
+/// are written in the order given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayout{
+    pub attributes:Vec<AttributeDesc>,
+    pub streams:Streams
+}
+
+/// The same position/tex coord/normal/tangent/color `f32` layout as
+/// `ObjLoader::vert_data`.
+impl Default for VertexLayout{
+    fn default()->Self{
+        VertexLayout {
+            attributes:vec![
+                AttributeDesc::new(Attribute::Position, Format::F32),
+                AttributeDesc::new(Attribute::TexCoord, Format::F32),
+                AttributeDesc::new(Attribute::TexCoord3, Format::F32),
+                AttributeDesc::new(Attribute::Normal, Format::F32),
+                AttributeDesc::new(Attribute::Tangent, Format::F32),
+                AttributeDesc::new(Attribute::Color, Format::F32)
+            ],
+            streams:Streams::Interleaved
+        }
+    }
+}
+
+/// Where one attribute ended up in the packed buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeInfo{
+    pub attribute:Attribute,
+    pub format:Format,
+    pub components:usize,
+    /// Index of the buffer the attribute lives in.
+    pub stream:usize,
+    /// Offset in bytes from the start of a vertex in that buffer.
+    pub offset:usize
+}
+
+/// A `VertexLayout` resolved against the data that's actually available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutInfo{
+    pub attributes:Vec<AttributeInfo>,
+    /// Stride in bytes of each buffer.
+    pub strides:Vec<usize>
+}
+
+impl LayoutInfo{
+    pub fn attribute(&self, attribute:Attribute)->Option<&AttributeInfo>{
+        self.attributes.iter().find(|info| info.attribute == attribute)
+    }
+}
+
+/// Vertex buffers built by `ObjLoader::pack`, indexed by `ObjLoader::indices`.
+/// Multi-byte values are little endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexBuffers{
+    pub info:LayoutInfo,
+    pub vertex_count:usize,
+    pub buffers:Vec<Vec<u8>>
+}
+
+/// Describes `ObjLoader::vert_data`: interleaved `f32`s with positions
+/// first, followed by whichever other attributes the obj has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexInfo{
+    pub vertex_count:usize,
+    /// Floats per vertex.
+    pub stride:usize,
+    pub stride_bytes:usize,
+    pub attributes:Vec<AttributeInfo>
+}
+
+impl VertexInfo{
+    pub(crate) fn new(vertex_count:usize, available:&[Attribute])->Self{
+        let info = VertexLayout::default().resolve(available);
+        let stride_bytes = info.strides.first().copied().unwrap_or(0);
+        VertexInfo { vertex_count, stride:stride_bytes / 4, stride_bytes, attributes:info.attributes }
+    }
+
+    /// Offset of an attribute in floats from the start of a vertex, `None` if
+    /// the data doesn't have it.
+    pub fn offset(&self, attribute:Attribute)->Option<usize>{
+        self.offset_bytes(attribute).map(|offset| offset / 4)
+    }
+
+    pub fn offset_bytes(&self, attribute:Attribute)->Option<usize>{
+        self.attributes.iter().find(|info| info.attribute == attribute).map(|info| info.offset)
+    }
+}
+
+impl VertexLayout{
+    /// Works out the stride and byte offsets of each attribute, given which
+    /// attributes have data. Every attribute starts on a 4 byte boundary and
+    /// strides are padded to a multiple of 4, as most graphics APIs require.
+    pub fn resolve(&self, available:&[Attribute])->LayoutInfo{
+        let mut attributes = Vec::new();
+        let mut strides = Vec::new();
+        for desc in &self.attributes{
+            if desc.presence == Presence::IfAvailable && !available.contains(&desc.attribute){
+                continue;
+            }
+            let stream = match self.streams{
+                Streams::Interleaved => 0,
+                Streams::Separate => strides.len()
+            };
+            if stream == strides.len(){
+                strides.push(0);
+            }
+            let components = desc.attribute.components();
+            attributes.push(AttributeInfo { attribute:desc.attribute, format:desc.format, components, stream, offset:strides[stream] });
+            strides[stream] = align4(strides[stream] + components * desc.format.size());
+        }
+        LayoutInfo { attributes, strides }
+    }
+}
+
+fn align4(n:usize)->usize{
+    (n + 3) & !3
+}
+
+pub(crate) fn pack(vertices:&[ObjObject], available:&[Attribute], layout:&VertexLayout)->VertexBuffers{
+    let info = layout.resolve(available);
+    let mut buffers:Vec<Vec<u8>> = info.strides.iter()
+        .map(|stride| Vec::with_capacity(stride * vertices.len()))
+        .collect();
+
+    for (i, vertex) in vertices.iter().enumerate(){
+        for attr in &info.attributes{
+            let buffer = &mut buffers[attr.stream];
+            // Pad up to the attribute's aligned offset
+            buffer.resize(i * info.strides[attr.stream] + attr.offset, 0);
+            let values = if available.contains(&attr.attribute) { vertex.attribute(attr.attribute) } else { [0.;4] };
+            for value in &values[..attr.components]{
+                attr.format.write(*value, buffer);
+            }
+        }
+        // Pad every buffer out to its stride
+        for (buffer, stride) in buffers.iter_mut().zip(&info.strides){
+            buffer.resize((i + 1) * stride, 0);
+        }
+    }
+
+    VertexBuffers { info, vertex_count:vertices.len(), buffers }
+}
+
+/// Converts to half precision, rounding to nearest even.
+fn f32_to_f16(value:f32)->u16{
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+
+    // INF AND NAN
+    if exp == 0xff{
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+    let half_exp = exp - 127 + 15;
+    // OVERFLOW TO INF
+    if half_exp >= 0x1f{
+        return sign | 0x7c00;
+    }
+    // SUBNORMAL OR ZERO
+    if half_exp <= 0{
+        if half_exp < -10{
+            return sign;
+        }
+        let m = mant | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        let half = m >> shift;
+        let rem = m & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let rounded = if rem > halfway || (rem == halfway && half & 1 == 1) { half + 1 } else { half };
+        return sign | rounded as u16;
+    }
+    // NORMAL, a carry out of the mantissa correctly bumps the exponent
+    let half = ((half_exp as u32) << 10) | (mant >> 13);
+    let rem = mant & 0x1fff;
+    let rounded = if rem > 0x1000 || (rem == 0x1000 && half & 1 == 1) { half + 1 } else { half };
+    sign | rounded as u16
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{LoadOptions, ObjLoader};
+
+    #[test]
+    fn f16_conversion(){
+        assert_eq!(f32_to_f16(0.), 0);
+        assert_eq!(f32_to_f16(-0.), 0x8000);
+        assert_eq!(f32_to_f16(1.), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.), 0xc000);
+        assert_eq!(f32_to_f16(65504.), 0x7bff);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 1);
+        assert_eq!(f32_to_f16(2f32.powi(-26)), 0);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7e00, 0x7e00);
+        // Halfway between 1 and the next half, rounds to even
+        assert_eq!(f32_to_f16(1. + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1. + 3. * 2f32.powi(-11)), 0x3c02);
+    }
+
+    #[test]
+    fn pack_mixed_formats(){
+        let src = "v 1 2 3\nv 4 5 6\nv 7 8 9\nvt 0.5 0.25 1\nvn 0 0 -1\nf 1/1/1 2/1/1 3/1/1\n";
+        let options = LoadOptions { tex_coord_w:true, ..Default::default() };
+        let loader = ObjLoader::from_str_with(src, &options).unwrap();
+        let layout = VertexLayout {
+            attributes:vec![
+                AttributeDesc::new(Attribute::Normal, Format::Snorm8),
+                AttributeDesc::new(Attribute::TexCoord3, Format::Unorm16),
+                AttributeDesc::new(Attribute::Position, Format::F32)
+            ],
+            streams:Streams::Interleaved
+        };
+        let packed = loader.pack(&layout);
+        let info = &packed.info;
+        let stride = info.strides[0];
+        assert_eq!(stride, 24);
+        assert_eq!(packed.buffers[0].len(), stride * 3);
+
+        let normal = info.attribute(Attribute::Normal).unwrap().offset;
+        let tex = info.attribute(Attribute::TexCoord3).unwrap().offset;
+        let pos = info.attribute(Attribute::Position).unwrap().offset;
+        for (i, vertex) in packed.buffers[0].chunks_exact(stride).enumerate(){
+            assert_eq!(vertex[normal..normal+3], [0, 0, (-127i8) as u8]);
+            let u16_at = |at:usize| u16::from_le_bytes([vertex[at], vertex[at+1]]);
+            assert_eq!([u16_at(tex), u16_at(tex+2), u16_at(tex+4)], [32768, 16384, 65535]);
+            let f32_at = |at:usize| f32::from_le_bytes(vertex[at..at+4].try_into().unwrap());
+            let expected = [1., 2., 3.].map(|c:f32| c + 3. * i as f32);
+            assert_eq!([f32_at(pos), f32_at(pos+4), f32_at(pos+8)], expected);
+        }
+    }
+}
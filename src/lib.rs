@@ -1,8 +1,247 @@
 mod obj_loader;
+mod obj_error;
+mod math;
+mod mtl;
+mod layout;
+mod triangulate;
+mod normals;
+mod tangents;
+mod statement;
+mod freeform;
+mod writer;
+mod document;
+
+pub use obj_error::{ObjError, Location, Slot, Warning};
+pub use mtl::{Material, TextureMap, TextureOptions, load_mtl, parse_mtl};
+pub use freeform::{CurveType, FreeformType, ControlPoint, Curve, Curve2, CurveRef, Surface, FreeformGeometry};
+pub use obj_loader::{ObjData, Face, FaceIndex, FaceIndices};
+pub use writer::{ObjWriter, write_mtl};
+pub use document::{ObjDocument, ObjStatement};
+pub use layout::{Attribute, Format, Presence, AttributeDesc, Streams, VertexLayout, AttributeInfo, LayoutInfo, VertexBuffers, VertexInfo};
+
+use std::path::PathBuf;
 
 pub struct ObjLoader{
     pub indices:Vec<u32>,
+    /// Corner count of each face in `indices`, only filled when loading with
+    /// `Primitive::Polygons`.
+    pub face_sizes:Vec<u32>,
+    /// Triangle list of the faces that weren't quads, only filled when
+    /// loading with `Primitive::Quads(NonQuads::Triangulate)`.
+    pub leftover_triangles:Vec<u32>,
+    /// Line list of the `l` polylines, each split into its segments.
+    pub line_indices:Vec<u32>,
+    /// The vertices of the `p` statements.
+    pub point_indices:Vec<u32>,
     pub vert_data:Vec<f32>,
     contains_tex_coords:bool,
-    contains_normals:bool
+    tex_coord_w:bool,
+    contains_normals:bool,
+    contains_tangents:bool,
+    contains_colors:bool,
+    skipped_faces:Vec<ObjError>,
+    warnings:Vec<Warning>,
+    materials:Vec<Material>,
+    material_ranges:Vec<MaterialRange>,
+    meshes:Vec<Mesh>,
+    freeform:FreeformGeometry,
+    primitive:Primitive,
+    origin:[f64;3],
+    vertices:Vec<obj_loader::ObjObject>
+}
+
+/// A run of faces that use one material. `material` indexes
+/// `ObjLoader::materials`, `None` for faces before any `usemtl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialRange{
+    pub material:Option<usize>,
+    /// Range of `ObjLoader::indices` the run covers.
+    pub start:usize,
+    pub count:usize,
+    /// Range of `ObjLoader::leftover_triangles` the run covers.
+    pub leftover_start:usize,
+    pub leftover_count:usize
+}
+
+/// What to do with a face that references a position, tex coord or normal
+/// that doesn't exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidFaces{
+    /// Stop loading and return the error.
+    #[default]
+    Fail,
+    /// Leave the face out, recording the error on the loader.
+    Skip
+}
+
+/// How to fill in the tex coord or normal of a face corner that doesn't give
+/// one, when other faces in the file do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissingAttribute<T>{
+    /// Use the given value.
+    Default(T),
+    /// Derive it from the face: its flat normal, or a planar projection of
+    /// its positions for tex coords.
+    Generate,
+    /// Stop loading with `ObjError::MissingAttribute`.
+    Error
+}
+
+/// A named part of the loaded obj, see `SplitBy`. Parts the mesh wasn't
+/// split on are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mesh{
+    /// The object, group and material names joined with `/`.
+    pub name:String,
+    pub object:Option<String>,
+    pub group:Option<String>,
+    /// Index into `ObjLoader::materials`.
+    pub material:Option<usize>,
+    /// Range of `ObjLoader::indices` the mesh covers.
+    pub start:usize,
+    pub count:usize,
+    /// Range of `ObjLoader::face_sizes` the mesh covers.
+    pub face_start:usize,
+    pub face_count:usize,
+    /// Range of `ObjLoader::leftover_triangles` the mesh covers.
+    pub leftover_start:usize,
+    pub leftover_count:usize
+}
+
+/// Which statements start a new `Mesh`. With nothing set the whole file is
+/// a single mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SplitBy{
+    /// `o`
+    pub objects:bool,
+    /// `g`
+    pub groups:bool,
+    /// `usemtl`
+    pub materials:bool
+}
+
+/// How faces with more than four corners are split into triangles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Triangulation{
+    /// Fan out from the first corner, only correct for convex faces.
+    #[default]
+    Fan,
+    /// Ear clipping on the face's best fit plane, handles concave faces.
+    EarClipping
+}
+
+/// What `ObjLoader::indices` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Primitive{
+    /// A triangle list.
+    #[default]
+    Triangles,
+    /// Every face's corners as written, with `ObjLoader::face_sizes` giving
+    /// how many corners each face has.
+    Polygons,
+    /// A quad list, for quad patch pipelines. Faces that aren't quads are
+    /// dealt with as the `NonQuads` says.
+    Quads(NonQuads)
+}
+
+/// What `Primitive::Quads` does with faces that don't have four corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonQuads{
+    /// Stop loading with `ObjError::NotAQuad`.
+    #[default]
+    Error,
+    /// Triangulate them into `ObjLoader::leftover_triangles`.
+    Triangulate,
+    /// Triangulate them and write each triangle as a quad with its last
+    /// corner repeated.
+    Degenerate
+}
+
+/// How much each face contributes to a generated vertex normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalWeighting{
+    /// By face area, large faces dominate.
+    #[default]
+    Area,
+    /// By the angle of the face's corner at the vertex, independent of how
+    /// the surface is tessellated.
+    Angle
+}
+
+/// Generating vertex normals from the geometry. By default faces in the same
+/// smoothing group (`s N`) share normals where they meet, so edges between
+/// groups stay hard, and faces with smoothing off (`s off` or `s 0`) are flat
+/// shaded.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GenerateNormals{
+    /// Also replace normals given in the file, rather than only generating
+    /// them for files that have none.
+    pub replace_existing:bool,
+    pub weighting:NormalWeighting,
+    /// Ignore smoothing groups and smooth across any edge whose faces are
+    /// within this many degrees of each other instead, for files that have
+    /// no `s` statements.
+    pub crease_angle:Option<f64>
+}
+
+/// Where to rebase positions around, for geometry far from the origin that
+/// would lose precision as `f32`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin{
+    /// The centre of the positions' bounding box.
+    Computed,
+    Supplied([f64;3])
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions{
+    pub invalid_faces:InvalidFaces,
+    pub missing_tex_coords:MissingAttribute<[f64;2]>,
+    pub missing_normals:MissingAttribute<[f64;3]>,
+    /// Read the `mtllib` files the obj references.
+    pub load_materials:bool,
+    /// Where to look for mtl libraries when loading from a reader or string,
+    /// files are always looked up next to the obj.
+    pub material_dir:Option<PathBuf>,
+    pub split:SplitBy,
+    pub triangulation:Triangulation,
+    pub primitive:Primitive,
+    pub generate_normals:Option<GenerateNormals>,
+    /// Generate MikkTSpace tangents, output as `Attribute::Tangent`.
+    /// Only done when the mesh has both tex coords and normals.
+    pub generate_tangents:bool,
+    /// Output tex coords with their `w` as `Attribute::TexCoord3`, rather
+    /// than just `u v`.
+    pub tex_coord_w:bool,
+    /// Fail with `ObjError::Unsupported` on statements the loader doesn't
+    /// support, rather than collecting them in `ObjLoader::warnings`.
+    pub strict:bool,
+    /// Tessellate free-form surfaces into triangles, and curves into
+    /// `ObjLoader::line_indices`, keeping roughly within this distance of the
+    /// true shape. Only bezier and b-spline types are supported.
+    pub tessellate_freeform:Option<f64>,
+    /// Move the positions so this point becomes the origin before they're
+    /// converted to `f32`, see `ObjLoader::origin`.
+    pub origin:Option<Origin>
+}
+
+impl Default for LoadOptions{
+    fn default()->Self{
+        LoadOptions {
+            invalid_faces:InvalidFaces::default(),
+            missing_tex_coords:MissingAttribute::Default([0., 0.]),
+            missing_normals:MissingAttribute::Generate,
+            load_materials:true,
+            material_dir:None,
+            split:SplitBy::default(),
+            triangulation:Triangulation::default(),
+            primitive:Primitive::default(),
+            generate_normals:None,
+            generate_tangents:false,
+            tex_coord_w:false,
+            strict:false,
+            tessellate_freeform:None,
+            origin:None
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+// Small vector helpers for the geometry work done while indexing. Kept on
+// plain arrays so they can be used on both parsed and generated data.
+
+pub type V3 = [f64;3];
+
+pub fn v3(v:&l_alg::Vec3)->V3{
+    [v.x, v.y, v.z]
+}
+
+pub fn add(a:V3, b:V3)->V3{
+    [a[0]+b[0], a[1]+b[1], a[2]+b[2]]
+}
+
+pub fn sub(a:V3, b:V3)->V3{
+    [a[0]-b[0], a[1]-b[1], a[2]-b[2]]
+}
+
+pub fn scale(a:V3, s:f64)->V3{
+    [a[0]*s, a[1]*s, a[2]*s]
+}
+
+pub fn dot(a:V3, b:V3)->f64{
+    a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+pub fn cross(a:V3, b:V3)->V3{
+    [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
+pub fn length(a:V3)->f64{
+    dot(a, a).sqrt()
+}
+
+/// Normalizes `a`, leaving degenerate (zero length) vectors untouched.
+pub fn normalize(a:V3)->V3{
+    let len = length(a);
+    if len > 0.{ scale(a, 1./len) } else { a }
+}
+
+/// Newell's method, works for non-planar and concave polygons. The length of
+/// the result is twice the polygon's area.
+pub fn polygon_normal(points:&[V3])->V3{
+    let mut n = [0.;3];
+    for (i, a) in points.iter().enumerate(){
+        let b = points[(i+1) % points.len()];
+        n[0] += (a[1]-b[1]) * (a[2]+b[2]);
+        n[1] += (a[2]-b[2]) * (a[0]+b[0]);
+        n[2] += (a[0]-b[0]) * (a[1]+b[1]);
+    }
+    n
+}
+
+/// Index of the largest component of `n`, ie. the axis a polygon with that
+/// normal should be projected along.
+pub fn dominant_axis(n:V3)->usize{
+    let a = [n[0].abs(), n[1].abs(), n[2].abs()];
+    if a[0] >= a[1] && a[0] >= a[2] { 0 } else if a[1] >= a[2] { 1 } else { 2 }
+}
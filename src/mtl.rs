@@ -0,0 +1,326 @@
+use std::{fs::File, io::{BufRead, BufReader}, path::{Path, PathBuf}, str::FromStr};
+use crate::*;
+use crate::statement::{statements, Token};
+
+/// A texture reference from a `map_*` (or `bump`, `disp`, `decal`, `refl`)
+/// statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureMap{
+    pub path:PathBuf,
+    pub options:TextureOptions
+}
+
+/// The `-option` arguments that can precede a texture's file name. Fields
+/// hold the spec's defaults when the option isn't given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureOptions{
+    /// `-blendu on|off`
+    pub blend_u:bool,
+    /// `-blendv on|off`
+    pub blend_v:bool,
+    /// `-bm mult`, bump maps only
+    pub bump_multiplier:f64,
+    /// `-boost value`
+    pub boost:Option<f64>,
+    /// `-cc on|off`
+    pub color_correction:bool,
+    /// `-clamp on|off`
+    pub clamp:bool,
+    /// `-imfchan r|g|b|m|l|z`
+    pub channel:Option<char>,
+    /// `-mm base gain`
+    pub range:[f64;2],
+    /// `-o u v w`
+    pub offset:[f64;3],
+    /// `-s u v w`
+    pub scale:[f64;3],
+    /// `-t u v w`
+    pub turbulence:[f64;3],
+    /// `-texres resolution`
+    pub resolution:Option<u32>,
+    /// `-type sphere|cube_top|...`, reflection maps only
+    pub reflection_type:Option<String>
+}
+
+impl Default for TextureOptions{
+    fn default()->Self{
+        TextureOptions {
+            blend_u:true,
+            blend_v:true,
+            bump_multiplier:1.,
+            boost:None,
+            color_correction:false,
+            clamp:false,
+            channel:None,
+            range:[0., 1.],
+            offset:[0.;3],
+            scale:[1.;3],
+            turbulence:[0.;3],
+            resolution:None,
+            reflection_type:None
+        }
+    }
+}
+
+/// A material from an mtl library. Values that weren't given in the library
+/// are left as `None`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Material{
+    pub name:String,
+    /// `Ka`
+    pub ambient:Option<[f64;3]>,
+    /// `Kd`
+    pub diffuse:Option<[f64;3]>,
+    /// `Ks`
+    pub specular:Option<[f64;3]>,
+    /// `Ke`
+    pub emissive:Option<[f64;3]>,
+    /// `Ns`
+    pub shininess:Option<f64>,
+    /// `d`, or `1 - Tr`
+    pub dissolve:Option<f64>,
+    /// `Ni`
+    pub optical_density:Option<f64>,
+    /// `illum`
+    pub illum:Option<u32>,
+    pub ambient_map:Option<TextureMap>,
+    pub diffuse_map:Option<TextureMap>,
+    pub specular_map:Option<TextureMap>,
+    pub emissive_map:Option<TextureMap>,
+    pub shininess_map:Option<TextureMap>,
+    pub dissolve_map:Option<TextureMap>,
+    /// `map_bump` or `bump`
+    pub bump_map:Option<TextureMap>,
+    /// `disp`
+    pub displacement_map:Option<TextureMap>,
+    /// `decal`
+    pub decal_map:Option<TextureMap>,
+    /// `refl`
+    pub reflection_map:Option<TextureMap>
+}
+
+impl Material{
+    pub fn new(name:&str)->Self{
+        Material { name:name.to_string(), ..Default::default() }
+    }
+}
+
+/// Loads an mtl library from a file, texture paths are resolved relative to
+/// `base_dir`.
+pub fn load_mtl(file_loc:&Path, base_dir:&Path)->Result<Vec<Material>, ObjError>{
+    let path = file_loc.to_string_lossy();
+    let file = File::open(file_loc)
+        .map_err(|source| ObjError::Io { loc:Location::new(Some(&path), 0, 0), source })?;
+    mtl_get_data(BufReader::new(file), Some(&path), base_dir)
+}
+
+/// Parses an mtl library from any buffered reader, texture paths are
+/// resolved relative to `base_dir`.
+pub fn parse_mtl<R:BufRead>(reader:R, base_dir:&Path)->Result<Vec<Material>, ObjError>{
+    mtl_get_data(reader, None, base_dir)
+}
+
+fn mtl_get_data<R:BufRead>(reader:R, path:Option<&str>, base_dir:&Path)->Result<Vec<Material>, ObjError>{
+    let mut materials:Vec<Material> = Vec::new();
+
+    for statement in statements(reader, path){
+        let statement = statement?;
+        let tokens = statement.tokens();
+        let Some(&first) = tokens.first() else { continue };
+        let keyword = first.text;
+        let args = &tokens[1..];
+        let too_few = |expected:usize| ObjError::ComponentCount {
+            loc:first.location(path),
+            keyword:keyword.to_string(),
+            expected,
+            found:args.len()
+        };
+
+        // NEW MATERIAL
+        if keyword == "newmtl"{
+            if args.is_empty(){
+                return Err(too_few(1));
+            }
+            materials.push(Material::new(&join_tokens(args)));
+            continue;
+        }
+        // Anything before the first `newmtl` has nothing to apply to
+        let Some(material) = materials.last_mut() else { continue };
+
+        let parse_float = |token:&Token|{
+            f64::from_str(token.text).map_err(|_| ObjError::BadFloat { loc:token.location(path), token:token.text.to_string() })
+        };
+        // Colors can be given as `r [g b]`, or `xyz x [y z]`. Spectral
+        // curves (`spectral file.rfl`) aren't supported and are left unset.
+        let parse_color = ||->Result<Option<[f64;3]>, ObjError>{
+            let values = match args.first().map(|token| token.text){
+                Some("spectral") => return Ok(None),
+                Some("xyz") => &args[1..],
+                _ => args
+            };
+            match values.len(){
+                0 => Err(too_few(1)),
+                1 | 2 => {
+                    let v = parse_float(&values[0])?;
+                    Ok(Some([v;3]))
+                }
+                _ => Ok(Some([parse_float(&values[0])?, parse_float(&values[1])?, parse_float(&values[2])?]))
+            }
+        };
+        let parse_scalar = ||{
+            args.first().ok_or_else(|| too_few(1)).and_then(parse_float)
+        };
+        let parse_map = ||{
+            parse_texture_map(args, base_dir, path).and_then(|map| map.ok_or_else(|| too_few(1)))
+        };
+
+        match keyword{
+            "Ka" => material.ambient = parse_color()?,
+            "Kd" => material.diffuse = parse_color()?,
+            "Ks" => material.specular = parse_color()?,
+            "Ke" => material.emissive = parse_color()?,
+            "Ns" => material.shininess = Some(parse_scalar()?),
+            "Ni" => material.optical_density = Some(parse_scalar()?),
+            // `-halo` only changes how dissolve is applied, the factor is last
+            "d" => material.dissolve = Some(args.last().ok_or_else(|| too_few(1)).and_then(parse_float)?),
+            "Tr" => material.dissolve = Some(1. - parse_scalar()?),
+            "illum" => {
+                let token = args.first().ok_or_else(|| too_few(1))?;
+                material.illum = Some(u32::from_str(token.text)
+                    .map_err(|_| ObjError::BadIndex { loc:token.location(path), token:token.text.to_string() })?);
+            }
+            "map_Ka" => material.ambient_map = Some(parse_map()?),
+            "map_Kd" => material.diffuse_map = Some(parse_map()?),
+            "map_Ks" => material.specular_map = Some(parse_map()?),
+            "map_Ke" => material.emissive_map = Some(parse_map()?),
+            "map_Ns" => material.shininess_map = Some(parse_map()?),
+            "map_d" => material.dissolve_map = Some(parse_map()?),
+            "map_bump" | "map_Bump" | "bump" => material.bump_map = Some(parse_map()?),
+            "disp" => material.displacement_map = Some(parse_map()?),
+            "decal" => material.decal_map = Some(parse_map()?),
+            "refl" => material.reflection_map = Some(parse_map()?),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Joins tokens back into a single string, for names and paths that may
+/// contain spaces.
+fn join_tokens(tokens:&[Token])->String{
+    tokens.iter().map(|t| t.text).collect::<Vec<_>>().join(" ")
+}
+
+/// Parses `[-option args...] file` into a texture map. Returns `None` when
+/// there is no file name after the options.
+fn parse_texture_map(args:&[Token], base_dir:&Path, path:Option<&str>)->Result<Option<TextureMap>, ObjError>{
+    let mut options = TextureOptions::default();
+    let mut i = 0;
+
+    let is_float = |i:usize| i < args.len() && f64::from_str(args[i].text).is_ok();
+    let parse_float = |i:usize|{
+        // A missing value is reported at the option it belongs to
+        let token = args.get(i).copied().unwrap_or(Token { text:"", ..args[i-1] });
+        f64::from_str(token.text).map_err(|_| ObjError::BadFloat { loc:token.location(path), token:token.text.to_string() })
+    };
+    let parse_on_off = |i:usize| args.get(i).map(|t| t.text != "off").unwrap_or(true);
+
+    while i < args.len() && args[i].text.starts_with('-') && !is_float(i){
+        let option = args[i].text;
+        i += 1;
+        match option{
+            "-blendu" => { options.blend_u = parse_on_off(i); i += 1; }
+            "-blendv" => { options.blend_v = parse_on_off(i); i += 1; }
+            "-cc" => { options.color_correction = parse_on_off(i); i += 1; }
+            "-clamp" => { options.clamp = parse_on_off(i); i += 1; }
+            "-bm" => { options.bump_multiplier = parse_float(i)?; i += 1; }
+            "-boost" => { options.boost = Some(parse_float(i)?); i += 1; }
+            "-imfchan" => { options.channel = args.get(i).and_then(|t| t.text.chars().next()); i += 1; }
+            "-texres" => { options.resolution = args.get(i).and_then(|t| u32::from_str(t.text).ok()); i += 1; }
+            "-type" => { options.reflection_type = args.get(i).map(|t| t.text.to_string()); i += 1; }
+            "-mm" => {
+                for value in &mut options.range{
+                    if is_float(i) && i+1 < args.len(){
+                        *value = parse_float(i)?;
+                        i += 1;
+                    }
+                }
+            }
+            "-o" | "-s" | "-t" => {
+                let target = match option{
+                    "-o" => &mut options.offset,
+                    "-s" => &mut options.scale,
+                    _ => &mut options.turbulence
+                };
+                // u is required, v and w are optional
+                for value in target.iter_mut(){
+                    if is_float(i) && i+1 < args.len(){
+                        *value = parse_float(i)?;
+                        i += 1;
+                    }
+                }
+            }
+            // Unknown option, skip it and hope it takes no arguments
+            _ => {}
+        }
+    }
+
+    if i >= args.len(){
+        return Ok(None);
+    }
+    Ok(Some(TextureMap { path:base_dir.join(join_tokens(&args[i..])), options }))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse(src:&str)->Vec<Material>{
+        parse_mtl(src.as_bytes(), Path::new("textures")).unwrap()
+    }
+
+    #[test]
+    fn colors_and_scalars(){
+        let materials = parse("Kd 1 0 0\nnewmtl red paint\nKa 0.5\nKd 1 0 0\nKs xyz 0.1 0.2 0.3\nKe spectral glow.rfl\nNs 10\nTr 0.25\nillum 2\n\
+            newmtl glass\nd -halo 0.5\nNi 1.5\n");
+        assert_eq!(materials.len(), 2);
+        let red = &materials[0];
+        assert_eq!(red.name, "red paint");
+        assert_eq!(red.ambient, Some([0.5;3]));
+        assert_eq!(red.diffuse, Some([1., 0., 0.]));
+        assert_eq!(red.specular, Some([0.1, 0.2, 0.3]));
+        assert_eq!(red.emissive, None);
+        assert_eq!(red.shininess, Some(10.));
+        assert_eq!(red.dissolve, Some(0.75));
+        assert_eq!(red.illum, Some(2));
+        assert_eq!(materials[1].dissolve, Some(0.5));
+        assert_eq!(materials[1].optical_density, Some(1.5));
+    }
+
+    #[test]
+    fn texture_options(){
+        let materials = parse("newmtl m\nmap_Kd -blendu off -o 0.5 0.25 -mm 0.1 0.9 -clamp on my texture.png\n\
+            bump -bm 2 -imfchan l bump.png\nrefl -type sphere -s 2 sky.png\n");
+        let diffuse = materials[0].diffuse_map.as_ref().unwrap();
+        assert_eq!(diffuse.path, Path::new("textures/my texture.png"));
+        assert_eq!(diffuse.options, TextureOptions {
+            blend_u:false,
+            offset:[0.5, 0.25, 0.],
+            range:[0.1, 0.9],
+            clamp:true,
+            ..Default::default()
+        });
+        let bump = materials[0].bump_map.as_ref().unwrap();
+        assert_eq!((bump.options.bump_multiplier, bump.options.channel), (2., Some('l')));
+        let refl = materials[0].reflection_map.as_ref().unwrap();
+        assert_eq!((refl.options.reflection_type.as_deref(), refl.options.scale), (Some("sphere"), [2., 1., 1.]));
+    }
+
+    #[test]
+    fn errors(){
+        assert!(matches!(parse_mtl("newmtl m\nKd 1 x 0\n".as_bytes(), Path::new("")), Err(ObjError::BadFloat { .. })));
+        assert!(matches!(parse_mtl("newmtl m\nmap_Kd -clamp on\n".as_bytes(), Path::new("")), Err(ObjError::ComponentCount { .. })));
+        assert!(matches!(parse_mtl("newmtl\n".as_bytes(), Path::new("")), Err(ObjError::ComponentCount { .. })));
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use crate::*;
+use crate::math::*;
+
+/// Normals generated for a set of faces: `corners[f][c]` indexes `normals`
+/// for corner `c` of face `f`.
+pub struct GeneratedNormals{
+    pub normals:Vec<V3>,
+    pub corners:Vec<Vec<usize>>
+}
+
+/// The face normal as contributed to one of its corners.
+fn corner_weight(points:&[V3], corner:usize, face_normal:V3, weighting:NormalWeighting)->V3{
+    match weighting{
+        // Newell's normal is already scaled by the face's area
+        NormalWeighting::Area => face_normal,
+        NormalWeighting::Angle => {
+            let n = points.len();
+            let p = points[corner];
+            let a = normalize(sub(points[(corner+n-1) % n], p));
+            let b = normalize(sub(points[(corner+1) % n], p));
+            scale(normalize(face_normal), dot(a, b).clamp(-1., 1.).acos())
+        }
+    }
+}
+
+/// Generates vertex normals from smoothing groups. Corners sharing a position
+/// and a non-zero group are averaged together, faces in group 0 (`s off`) are
+/// flat shaded.
+pub fn smoothing_group_normals(positions:&[V3], faces:&[Vec<usize>], groups:&[u32], weighting:NormalWeighting)->GeneratedNormals{
+    let mut normals:Vec<V3> = Vec::new();
+    let mut corners = Vec::with_capacity(faces.len());
+    let mut shared:HashMap<(usize, u32), usize> = HashMap::new();
+
+    for (face, &group) in faces.iter().zip(groups){
+        let points:Vec<V3> = face.iter().map(|&p| positions[p]).collect();
+        let face_normal = polygon_normal(&points);
+        if group == 0{
+            normals.push(normalize(face_normal));
+            corners.push(vec![normals.len()-1; face.len()]);
+            continue;
+        }
+        let mut face_corners = Vec::with_capacity(face.len());
+        for (corner, &pos) in face.iter().enumerate(){
+            let slot = *shared.entry((pos, group)).or_insert_with(|| {
+                normals.push([0.;3]);
+                normals.len()-1
+            });
+            normals[slot] = add(normals[slot], corner_weight(&points, corner, face_normal, weighting));
+            face_corners.push(slot);
+        }
+        corners.push(face_corners);
+    }
+
+    for normal in &mut normals{
+        *normal = normalize(*normal);
+    }
+    GeneratedNormals { normals, corners }
+}
+
+/// Generates vertex normals by crease angle, ignoring smoothing groups. Each
+/// corner averages the faces around its position whose normals are within
+/// `crease_angle` degrees of its own face's, so sharper edges get split
+/// vertices.
+pub fn crease_angle_normals(positions:&[V3], faces:&[Vec<usize>], crease_angle:f64, weighting:NormalWeighting)->GeneratedNormals{
+    let min_dot = crease_angle.to_radians().cos();
+
+    // GATHER FACE CONTRIBUTIONS PER POSITION
+    let mut face_normals:Vec<V3> = Vec::with_capacity(faces.len());
+    let mut around:HashMap<usize, Vec<(usize, V3)>> = HashMap::new();
+    for (f, face) in faces.iter().enumerate(){
+        let points:Vec<V3> = face.iter().map(|&p| positions[p]).collect();
+        let face_normal = polygon_normal(&points);
+        face_normals.push(normalize(face_normal));
+        for (corner, &pos) in face.iter().enumerate(){
+            around.entry(pos).or_default().push((f, corner_weight(&points, corner, face_normal, weighting)));
+        }
+    }
+
+    // Corners whose faces pick the same neighbours end up with the same
+    // normal, so they share a slot
+    let mut normals:Vec<V3> = Vec::new();
+    let mut corners = Vec::with_capacity(faces.len());
+    let mut shared:HashMap<(usize, Vec<usize>), usize> = HashMap::new();
+    for (f, face) in faces.iter().enumerate(){
+        let mut face_corners = Vec::with_capacity(face.len());
+        for &pos in face{
+            let neighbours:Vec<&(usize, V3)> = around[&pos].iter()
+                .filter(|(other, _)| *other == f || dot(face_normals[f], face_normals[*other]) >= min_dot)
+                .collect();
+            let mut key:Vec<usize> = neighbours.iter().map(|(other, _)| *other).collect();
+            key.sort_unstable();
+            key.dedup();
+            let slot = *shared.entry((pos, key)).or_insert_with(|| {
+                let sum = neighbours.iter().fold([0.;3], |sum, (_, weight)| add(sum, *weight));
+                normals.push(normalize(sum));
+                normals.len()-1
+            });
+            face_corners.push(slot);
+        }
+        corners.push(face_corners);
+    }
+
+    GeneratedNormals { normals, corners }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// A unit square facing `+z` folded along `x = 1` into a `depth` deep
+    /// one facing `+x`.
+    fn fold(depth:f64)->(Vec<V3>, Vec<Vec<usize>>){
+        let positions = vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.], [1., 0., -depth], [1., 1., -depth]];
+        (positions, vec![vec![0, 1, 2, 3], vec![1, 4, 5, 2]])
+    }
+
+    fn assert_close(a:V3, b:V3){
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9), "{a:?} {b:?}");
+    }
+
+    #[test]
+    fn smoothing_group_shares_normals(){
+        let (positions, faces) = fold(1.);
+        let generated = smoothing_group_normals(&positions, &faces, &[1, 1], NormalWeighting::Area);
+        assert_eq!(generated.corners, [vec![0, 1, 2, 3], vec![1, 4, 5, 2]]);
+        let h = 0.5f64.sqrt();
+        assert_close(generated.normals[1], [h, 0., h]);
+        assert_close(generated.normals[2], [h, 0., h]);
+        assert_close(generated.normals[0], [0., 0., 1.]);
+        assert_close(generated.normals[4], [1., 0., 0.]);
+    }
+
+    #[test]
+    fn smoothing_off_is_flat(){
+        let (positions, faces) = fold(1.);
+        let generated = smoothing_group_normals(&positions, &faces, &[0, 0], NormalWeighting::Area);
+        assert_eq!(generated.corners, [vec![0; 4], vec![1; 4]]);
+        assert_close(generated.normals[0], [0., 0., 1.]);
+        assert_close(generated.normals[1], [1., 0., 0.]);
+
+        // Nor do different groups share
+        let generated = smoothing_group_normals(&positions, &faces, &[1, 2], NormalWeighting::Area);
+        assert_eq!(generated.normals.len(), 8);
+    }
+
+    #[test]
+    fn area_and_angle_weighting(){
+        // The second face is twice the area, both have right angles at the
+        // shared corners
+        let (positions, faces) = fold(2.);
+        let area = smoothing_group_normals(&positions, &faces, &[1, 1], NormalWeighting::Area);
+        let angle = smoothing_group_normals(&positions, &faces, &[1, 1], NormalWeighting::Angle);
+        assert_close(area.normals[1], normalize([2., 0., 1.]));
+        assert_close(angle.normals[1], normalize([1., 0., 1.]));
+    }
+
+    /// The three faces around a cube's corner at the origin, facing out of
+    /// the positive octant.
+    fn cube_corner()->(Vec<V3>, Vec<Vec<usize>>){
+        let positions = vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.], [1., 1., 0.], [0., 1., 1.], [1., 0., 1.]];
+        (positions, vec![vec![0, 2, 4, 1], vec![0, 1, 6, 3], vec![0, 3, 5, 2]])
+    }
+
+    #[test]
+    fn crease_angle_splits_sharp_edges(){
+        let (positions, faces) = cube_corner();
+        let generated = crease_angle_normals(&positions, &faces, 30., NormalWeighting::Area);
+        let corner:Vec<usize> = generated.corners.iter().map(|c| c[0]).collect();
+        assert_eq!(generated.normals.len(), 12);
+        assert_close(generated.normals[corner[0]], [0., 0., -1.]);
+        assert_close(generated.normals[corner[1]], [0., -1., 0.]);
+        assert_close(generated.normals[corner[2]], [-1., 0., 0.]);
+    }
+
+    #[test]
+    fn crease_angle_shares_smooth_edges(){
+        let (positions, faces) = cube_corner();
+        let generated = crease_angle_normals(&positions, &faces, 100., NormalWeighting::Area);
+        assert!(generated.corners.iter().all(|c| c[0] == generated.corners[0][0]));
+        assert_close(generated.normals[generated.corners[0][0]], normalize([-1., -1., -1.]));
+        // Edges are shared by the two faces along them
+        assert_eq!(generated.corners[0][3], generated.corners[1][1]);
+        assert_close(generated.normals[generated.corners[0][3]], normalize([0., -1., -1.]));
+        assert_eq!(generated.normals.len(), 7);
+    }
+}
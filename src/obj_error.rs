@@ -0,0 +1,154 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Where in the source an error occurred. Lines and columns are 1-based, a
+/// line of 0 means the error is not tied to a particular line (eg. the file
+/// could not be opened).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location{
+    pub path:Option<String>,
+    pub line:usize,
+    pub column:usize
+}
+
+impl Location{
+    pub fn new(path:Option<&str>, line:usize, column:usize)->Self{
+        Location { path:path.map(str::to_owned), line, column }
+    }
+}
+
+impl fmt::Display for Location{
+    fn fmt(&self, f:&mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}:{}:{}", self.path.as_deref().unwrap_or("<obj>"), self.line, self.column)
+    }
+}
+
+/// Which component of a face corner (`pos/tex/norm`) an index belongs to,
+/// or which free-form element it refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot{
+    Position,
+    TexCoord,
+    Normal,
+    /// A `vp`, from `curv2` and `sp`.
+    ParameterVertex,
+    /// A `curv2`, from `trim`, `hole` and `scrv`.
+    Curve2
+}
+
+impl fmt::Display for Slot{
+    fn fmt(&self, f:&mut fmt::Formatter<'_>)->fmt::Result{
+        f.write_str(match self{
+            Slot::Position => "position",
+            Slot::TexCoord => "tex coord",
+            Slot::Normal => "normal",
+            Slot::ParameterVertex => "parameter vertex",
+            Slot::Curve2 => "trimming curve"
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError{
+    /// Reading the source failed.
+    Io{ loc:Location, source:io::Error },
+    /// A token that should have been a number could not be parsed as one.
+    BadFloat{ loc:Location, token:String },
+    /// A face index token could not be parsed as an integer.
+    BadIndex{ loc:Location, token:String },
+    /// A statement had fewer components than it requires.
+    ComponentCount{ loc:Location, keyword:String, expected:usize, found:usize },
+    /// A face references an element that does not exist.
+    OutOfRange{ loc:Location, slot:Slot, index:i64, len:usize },
+    /// A face corner has no tex coord or normal while other faces do, and
+    /// the options say not to fill it in.
+    MissingAttribute{ loc:Location, slot:Slot },
+    /// A face that isn't a quad was found while loading quads only.
+    NotAQuad{ loc:Location, corners:usize },
+    /// A statement the loader doesn't support, only an error in strict mode.
+    Unsupported{ loc:Location, keyword:String }
+}
+
+impl ObjError{
+    pub fn location(&self)->&Location{
+        match self{
+            ObjError::Io { loc, .. } |
+            ObjError::BadFloat { loc, .. } |
+            ObjError::BadIndex { loc, .. } |
+            ObjError::ComponentCount { loc, .. } |
+            ObjError::OutOfRange { loc, .. } |
+            ObjError::MissingAttribute { loc, .. } |
+            ObjError::NotAQuad { loc, .. } |
+            ObjError::Unsupported { loc, .. } => loc
+        }
+    }
+}
+
+impl fmt::Display for ObjError{
+    fn fmt(&self, f:&mut fmt::Formatter<'_>)->fmt::Result{
+        match self{
+            ObjError::Io { loc, source } =>
+                write!(f, "{loc}: i/o error: {source}"),
+            ObjError::BadFloat { loc, token } =>
+                write!(f, "{loc}: invalid number '{token}'"),
+            ObjError::BadIndex { loc, token } =>
+                write!(f, "{loc}: invalid index '{token}'"),
+            ObjError::ComponentCount { loc, keyword, expected, found } =>
+                write!(f, "{loc}: '{keyword}' expects at least {expected} components, found {found}"),
+            ObjError::OutOfRange { loc, slot, index, len } =>
+                write!(f, "{loc}: {slot} index {index} is out of range (only {len} defined)"),
+            ObjError::MissingAttribute { loc, slot } =>
+                write!(f, "{loc}: face corner has no {slot} while other faces do"),
+            ObjError::NotAQuad { loc, corners } =>
+                write!(f, "{loc}: face has {corners} corners, only quads are allowed"),
+            ObjError::Unsupported { loc, keyword } =>
+                write!(f, "{loc}: unsupported statement '{keyword}'")
+        }
+    }
+}
+
+/// Something in the source that was ignored while loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning{
+    /// Statements with a keyword the loader doesn't support. `loc` is the
+    /// first one, `count` how many there were in total.
+    Unsupported{ loc:Location, keyword:String, count:usize },
+    /// An `mtllib` that doesn't exist, its materials are left blank.
+    MissingMaterialLib{ loc:Location, path:PathBuf },
+    /// A free-form `curv` or `surf` that couldn't be tessellated, because of
+    /// its type or parameters.
+    Untessellated{ loc:Location, keyword:String }
+}
+
+impl Warning{
+    pub fn location(&self)->&Location{
+        match self{
+            Warning::Unsupported { loc, .. } |
+            Warning::MissingMaterialLib { loc, .. } |
+            Warning::Untessellated { loc, .. } => loc
+        }
+    }
+}
+
+impl fmt::Display for Warning{
+    fn fmt(&self, f:&mut fmt::Formatter<'_>)->fmt::Result{
+        match self{
+            Warning::Unsupported { loc, keyword, count: 1 } =>
+                write!(f, "{loc}: unsupported statement '{keyword}'"),
+            Warning::Unsupported { loc, keyword, count } =>
+                write!(f, "{loc}: unsupported statement '{keyword}' ({count} times)"),
+            Warning::MissingMaterialLib { loc, path } =>
+                write!(f, "{loc}: material library '{}' not found", path.display()),
+            Warning::Untessellated { loc, keyword } =>
+                write!(f, "{loc}: could not tessellate '{keyword}', only bezier and b-spline types with matching parameters are supported")
+        }
+    }
+}
+
+impl Error for ObjError{
+    fn source(&self)->Option<&(dyn Error + 'static)>{
+        match self{
+            ObjError::Io { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
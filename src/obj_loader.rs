@@ -1,197 +1,1018 @@
-use std::{collections::HashMap, error::Error, fs::File, io::{BufRead, BufReader}, str::FromStr};
-use l_alg::{Vec3, Vec2};
+use std::{collections::HashMap, fs::File, io::{BufRead, BufReader, ErrorKind}, path::Path, str::FromStr};
+use l_alg::Vec3;
 use crate::*;
+use crate::math::*;
+use crate::statement::{statements, Token};
+use crate::freeform::FreeformParser;
 
+/// One corner of a face. Indices are resolved to 0-based positions in the
+/// parsed arrays, tex and norm are `None` when the corner doesn't give one.
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
-struct FaceIndex{
-    pos:i32,
-    tex:i32,
-    norm:i32
+pub struct FaceIndex{
+    pub pos:usize,
+    pub tex:Option<usize>,
+    pub norm:Option<usize>
 }
 
-type FaceIndices = Vec<FaceIndex>;
+pub type FaceIndices = Vec<FaceIndex>;
 
+/// The object, group and material a face is bucketed under when splitting.
+type MeshKey = (Option<usize>, Option<usize>, Option<usize>);
+
+/// A single `f` statement along with where it came from, so problems found
+/// while indexing can still be reported against the source. Also used for
+/// the vertices of `l` and `p` statements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Face{
+    pub indices:FaceIndices,
+    /// Line and column of each corner, a continued statement can put
+    /// corners on different lines.
+    pub corners:Vec<(usize, usize)>,
+    /// Index into `ObjData::material_names` of the `usemtl` in effect.
+    pub material:Option<usize>,
+    /// Index into `ObjData::objects` of the `o` in effect.
+    pub object:Option<usize>,
+    /// Index into `ObjData::groups` of the `g` in effect.
+    pub group:Option<usize>,
+    /// The `s` group in effect, 0 when smoothing is off.
+    pub smoothing:u32
+}
+
+/// An obj as parsed, before any indexing. Can be modified and then indexed
+/// with `ObjLoader::from_data`.
 #[derive(Debug)]
-struct ObjData{
-    vert_positions:Vec<Vec3>,
-    tex_coords:Vec<Vec2>,
-    normals:Vec<Vec3>,
-    faces: Vec<FaceIndices>
+pub struct ObjData{
+    /// The file the data was read from, used for error locations and for
+    /// finding mtl libraries.
+    pub path:Option<String>,
+    pub vert_positions:Vec<Vec3>,
+    /// RGBA per position from `v x y z r g b [a]`, empty when no `v` has a
+    /// color. Positions without one are white.
+    pub vert_colors:Vec<[f64;4]>,
+    /// Optional `w` of each `v`, empty when no `v` has one. Positions
+    /// without one have a weight of 1.
+    pub vert_weights:Vec<f64>,
+    /// `vt u [v [w]]`, with missing components 0.
+    pub tex_coords:Vec<Vec3>,
+    pub normals:Vec<Vec3>,
+    pub faces: Vec<Face>,
+    /// `l` polylines. Lines and points have no normals, `norm` is ignored.
+    pub lines:Vec<Face>,
+    /// `p` statements, which can list several points.
+    pub points:Vec<Face>,
+    /// Faces dropped while parsing, see `InvalidFaces::Skip`.
+    pub skipped_faces:Vec<ObjError>,
+    /// `mtllib` paths and where they were given.
+    pub material_libs:Vec<(String, Location)>,
+    /// `usemtl` names, in order of first use.
+    pub material_names:Vec<String>,
+    /// `o` names, in order of first use.
+    pub objects:Vec<String>,
+    /// `g` statements, a statement naming several groups is kept as one
+    /// entry with the names separated by spaces.
+    pub groups:Vec<String>,
+    pub freeform:FreeformGeometry,
+    pub warnings:Vec<Warning>
 }
 
 impl FaceIndex{
-    pub fn new(pos:i32, tex:i32, norm:i32)->Self{
+    pub fn new(pos:usize, tex:Option<usize>, norm:Option<usize>)->Self{
         FaceIndex { pos, tex, norm}
     }
 }
 
+impl Face{
+    /// Where a corner came from, faces built by hand may not say.
+    fn location(&self, path:Option<&str>, corner:usize)->Location{
+        let (line, column) = self.corners.get(corner).copied().unwrap_or_default();
+        Location::new(path, line, column)
+    }
+}
+
 impl ObjData{
-    pub fn new(vert_positions:Vec<Vec3>, tex_coords:Vec<Vec2>, normals:Vec<Vec3>, faces:Vec<FaceIndices>)->Self{
-        ObjData{vert_positions, tex_coords, normals, faces}
+    pub fn new(path:Option<String>, vert_positions:Vec<Vec3>, tex_coords:Vec<Vec3>, normals:Vec<Vec3>, faces:Vec<Face>)->Self{
+        ObjData{path, vert_positions, vert_colors:Vec::new(), vert_weights:Vec::new(), tex_coords, normals, faces, lines:Vec::new(), points:Vec::new(), skipped_faces:Vec::new(), material_libs:Vec::new(), material_names:Vec::new(), objects:Vec::new(), groups:Vec::new(), freeform:FreeformGeometry::default(), warnings:Vec::new()}
+    }
+
+    /// Parses the obj file at `file_loc` without indexing it. Only the
+    /// parsing options (`invalid_faces` and `strict`) are used.
+    pub fn from_file(file_loc:&str, options:&LoadOptions)->Result<Self, ObjError>{
+        let file = File::open(file_loc)
+            .map_err(|source| ObjError::Io { loc:Location::new(Some(file_loc), 0, 0), source })?;
+        obj_get_data(BufReader::new(file), Some(file_loc), options)
+    }
+
+    /// Parses obj data from any buffered reader without indexing it.
+    pub fn from_reader<R:BufRead>(reader:R, options:&LoadOptions)->Result<Self, ObjError>{
+        obj_get_data(reader, None, options)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-struct ObjObject{
+pub(crate) struct ObjObject{
     index:usize,
     pos: Vec3,
-    tex_coord:Vec2,
-    normal:Vec3
+    tex_coord:Vec3,
+    normal:Vec3,
+    tangent:[f64;4],
+    color:[f64;4]
 }
 
 impl ObjObject{
     pub fn new(index:usize, pos:&Vec3)->Self{
-        ObjObject { index, pos:pos.clone(), tex_coord:Vec2::new(0.,0.), normal: Vec3::new(0.,0.,0.)}
+        ObjObject { index, pos:pos.clone(), tex_coord:Vec3::new(0.,0.,0.), normal: Vec3::new(0.,0.,0.), tangent:[0.;4], color:[1.;4]}
+    }
+
+    /// The components of one attribute, padded out to four values.
+    pub fn attribute(&self, attribute:Attribute)->[f64;4]{
+        match attribute{
+            Attribute::Position => [self.pos.x, self.pos.y, self.pos.z, 0.],
+            Attribute::TexCoord => [self.tex_coord.x, self.tex_coord.y, 0., 0.],
+            Attribute::TexCoord3 => [self.tex_coord.x, self.tex_coord.y, self.tex_coord.z, 0.],
+            Attribute::Normal => [self.normal.x, self.normal.y, self.normal.z, 0.],
+            Attribute::Tangent => self.tangent,
+            Attribute::Color => self.color
+        }
     }
 }
 
-fn obj_get_data(file_loc:&str)->Result<ObjData, Box<dyn Error>>{
-    // BREAK FILE INTO LINE STRINGS
-    let file = File::open(file_loc).unwrap();
-    let lines = BufReader::new(file).lines();
+/// Resolves one index of a statement. Positive indices are 1-based, negative
+/// ones count back from the most recently defined element (-1 is the last
+/// one), `count` being how many have been defined so far.
+pub(crate) fn parse_index(token:&Token, part:&str, slot:Slot, count:usize, path:Option<&str>)->Result<usize, ObjError>{
+    let index = i32::from_str(part).ok().filter(|i| *i != 0).ok_or_else(|| ObjError::BadIndex {
+        loc:token.location(path),
+        token:token.text.to_string()
+    })?;
+    if index > 0{
+        return Ok(index as usize - 1);
+    }
+    let resolved = count as i64 + index as i64;
+    if resolved < 0{
+        return Err(ObjError::OutOfRange { loc:token.location(path), slot, index:index as i64, len:count });
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses the corners of an `f`, `l` or `p` statement, which needs at least
+/// `min` of them. `counts` holds how many positions, tex coords and normals
+/// have been defined so far, which is what negative (relative) indices are
+/// resolved against.
+fn parse_face(tokens:&[Token], path:Option<&str>, counts:[usize;3], min:usize)->Result<Face, ObjError>{
+    if tokens.len() < min+1{
+        return Err(ObjError::ComponentCount {
+            loc:tokens[0].location(path),
+            keyword:tokens[0].text.to_string(),
+            expected:min,
+            found:tokens.len()-1
+        });
+    }
 
-    // PARSE ANY LINES THAT IS MADE UP OF FLOATS
-    let parse_floats = |s:&str| {
+    let mut face = Face { indices:FaceIndices::new(), corners:Vec::new(), material:None, object:None, group:None, smoothing:0 };
+    for token in &tokens[1..]{
+        let parts:Vec<&str> = token.text.split('/').collect();
+        let mut face_ind = FaceIndex::new(parse_index(token, parts[0], Slot::Position, counts[0], path)?, None, None);
+        if parts.len() > 1 && !parts[1].is_empty(){
+            face_ind.tex = Some(parse_index(token, parts[1], Slot::TexCoord, counts[1], path)?);
+        }
+        // Lines and points have no normals (`l v/vt`, `p v`), one given
+        // anyway is ignored
+        if parts.len() > 2 && !parts[2].is_empty() && tokens[0].text == "f"{
+            face_ind.norm = Some(parse_index(token, parts[2], Slot::Normal, counts[2], path)?);
+        }
+        face.indices.push(face_ind);
+        face.corners.push((token.line, token.column));
+    }
+    Ok(face)
+}
+
+fn obj_get_data<R:BufRead>(reader:R, path:Option<&str>, options:&LoadOptions)->Result<ObjData, ObjError>{
+    // PARSE ANY STATEMENT THAT IS MADE UP OF FLOATS
+    let parse_floats = |tokens:&[Token], min:usize|->Result<Vec<f64>, ObjError>{
         let mut nums:Vec<f64> = Vec::new();
-        let num_strs = s.split_whitespace();
-        for num_str in num_strs{
-            nums.push(f64::from_str(num_str).unwrap());
+        for token in &tokens[1..]{
+            let num = f64::from_str(token.text).map_err(|_| ObjError::BadFloat {
+                loc:token.location(path),
+                token:token.text.to_string()
+            })?;
+            nums.push(num);
         }
-        nums
+        if nums.len() < min{
+            return Err(ObjError::ComponentCount {
+                loc:tokens[0].location(path),
+                keyword:tokens[0].text.to_string(),
+                expected:min,
+                found:nums.len()
+            });
+        }
+        Ok(nums)
     };
 
     // DATA VECS
     let mut verts:Vec<Vec3> = Vec::new();
-    let mut tex_coords:Vec<Vec2> = Vec::new();
+    let mut colors:Vec<[f64;4]> = Vec::new();
+    let mut weights:Vec<f64> = Vec::new();
+    let mut tex_coords:Vec<Vec3> = Vec::new();
     let mut normals:Vec<Vec3> = Vec::new();
-    let mut faces:Vec<FaceIndices> = Vec::new(); 
+    let mut faces:Vec<Face> = Vec::new(); 
+    let mut lines:Vec<Face> = Vec::new();
+    let mut points:Vec<Face> = Vec::new();
+    let mut skipped_faces:Vec<ObjError> = Vec::new();
+    let mut material_libs:Vec<(String, Location)> = Vec::new();
+    let mut material_names:Vec<String> = Vec::new();
+    let mut material:Option<usize> = None;
+    let mut objects:Vec<String> = Vec::new();
+    let mut object:Option<usize> = None;
+    let mut groups:Vec<String> = Vec::new();
+    let mut group:Option<usize> = None;
+    let mut smoothing:u32 = 0;
+    let mut warnings:Vec<Warning> = Vec::new();
+    let mut freeform = FreeformParser::default();
 
-    // DATA GATHERING: Iterate over line strings
-    for line in lines{
-        let line_str = line.unwrap();
-        // POSITIONS
-        if line_str.find("v ") == Some(0){
-            let line_slc = &line_str[2..];
+    // DATA GATHERING: Iterate over statements
+    for statement in statements(reader, path){
+        let statement = statement?;
+        let tokens = statement.tokens();
+        let Some(keyword) = tokens.first().map(|token| token.text) else { continue };
 
-            let floats = parse_floats(line_slc);
+        // POSITIONS
+        if keyword == "v"{
+            let floats = parse_floats(&tokens, 3)?;
             verts.push(Vec3::new(floats[0], floats[1], floats[2]));
+            // Vertex colors as written by MeshLab, ZBrush etc.
+            let color = match floats.len(){
+                6 => Some([floats[3], floats[4], floats[5], 1.]),
+                7.. => Some([floats[3], floats[4], floats[5], floats[6]]),
+                _ => None
+            };
+            if color.is_some() && colors.is_empty(){
+                colors.resize(verts.len()-1, [1.;4]);
+            }
+            if color.is_some() || !colors.is_empty(){
+                colors.push(color.unwrap_or([1.;4]));
+            }
+            // Weight for rational curves and surfaces, `v x y z w`
+            let weight = if floats.len() == 4 { Some(floats[3]) } else { None };
+            if weight.is_some() && weights.is_empty(){
+                weights.resize(verts.len()-1, 1.);
+            }
+            if weight.is_some() || !weights.is_empty(){
+                weights.push(weight.unwrap_or(1.));
+            }
         }
-        // TEX COORDS
-        else if line_str.find("vt ") == Some(0){
-            let line_slc = &line_str[3..];
-
-            let floats = parse_floats(line_slc);
-            tex_coords.push(Vec2::new(floats[0], floats[1]));
+        // TEX COORDS, `vt u [v [w]]`
+        else if keyword == "vt"{
+            let floats = parse_floats(&tokens, 1)?;
+            let component = |i:usize| floats.get(i).copied().unwrap_or(0.);
+            tex_coords.push(Vec3::new(component(0), component(1), component(2)));
         }
         // NORMS
-        else if line_str.find("vn ") == Some(0){
-            let line_slc = &line_str[3..];
-
-            let floats = parse_floats(line_slc);
+        else if keyword == "vn"{
+            let floats = parse_floats(&tokens, 3)?;
             normals.push(Vec3::new(floats[0], floats[1], floats[2]));
         }
-        // FACE INDICES (pos/tex/norm)
-        else if line_str.find("f ") == Some(0){
-            let line_slc = &line_str[2..];
-
-            let mut face_indices = FaceIndices::new();
-            let str_indices = line_slc.split_whitespace();
-
-            for str_index in str_indices{
-                let parts:Vec<&str> = str_index.split("/").collect();
-                let mut face_ind = FaceIndex::new(0,0,0);
-                if parts.len() > 0 {
-                    face_ind.pos = i32::from_str(parts[0]).unwrap()-1;
+        // FACE, LINE AND POINT INDICES (pos/tex/norm)
+        else if keyword == "f" || keyword == "l" || keyword == "p"{
+            let counts = [verts.len(), tex_coords.len(), normals.len()];
+            let (elements, min) = match keyword{
+                "f" => (&mut faces, 3),
+                "l" => (&mut lines, 2),
+                _ => (&mut points, 1)
+            };
+            match parse_face(&tokens, path, counts, min){
+                Ok(face) => elements.push(Face { material, object, group, smoothing, ..face }),
+                Err(err @ ObjError::OutOfRange { .. }) if options.invalid_faces == InvalidFaces::Skip =>
+                    skipped_faces.push(err),
+                Err(err) => return Err(err)
+            }
+        }
+        // MATERIALS
+        else if keyword == "mtllib"{
+            material_libs.extend(tokens[1..].iter().map(|lib| (lib.text.to_string(), lib.location(path))));
+        }
+        else if keyword == "usemtl"{
+            // A bare `usemtl` goes back to no material, like a bare `g`
+            material = if tokens.len() > 1 { Some(intern(&mut material_names, &tokens[1..])) } else { None };
+        }
+        // OBJECTS AND GROUPS
+        else if keyword == "o"{
+            object = Some(intern(&mut objects, &tokens[1..]));
+        }
+        else if keyword == "s"{
+            // `s off` and `s 0` both turn smoothing off
+            smoothing = match tokens.get(1){
+                None => 0,
+                Some(token) if token.text == "off" => 0,
+                Some(token) => u32::from_str(token.text).map_err(|_| ObjError::BadIndex {
+                    loc:token.location(path),
+                    token:token.text.to_string()
+                })?
+            };
+        }
+        else if keyword == "g"{
+            // A bare `g` goes back to the default group
+            group = if tokens.len() > 1 { Some(intern(&mut groups, &tokens[1..])) } else { None };
+        }
+        // FREE-FORM CURVES AND SURFACES, a value the parser doesn't support
+        // (`cstype foo`) is treated like an unsupported statement
+        else{
+            let handled = match freeform.statement(&tokens, path, [verts.len(), tex_coords.len(), normals.len()]){
+                Err(ObjError::Unsupported { loc, keyword }) if !options.strict => {
+                    unsupported(&mut warnings, loc, &keyword);
+                    true
                 }
-                if parts.len() > 1 && parts[1] != ""{
-                    face_ind.tex = i32::from_str(parts[1]).unwrap()-1;
+                handled => handled?
+            };
+            if handled{
+                if keyword == "surf" && let Some(surface) = freeform.geometry.surfaces.last_mut(){
+                    surface.material = material;
+                    surface.object = object;
+                    surface.group = group;
+                    surface.smoothing = smoothing;
                 }
-                if parts.len() > 2 {
-                    face_ind.norm = i32::from_str(parts[2]).unwrap()-1;
+            }
+            // UNSUPPORTED
+            else if options.strict{
+                return Err(ObjError::Unsupported { loc:tokens[0].location(path), keyword:keyword.to_string() });
+            }
+            else{
+                unsupported(&mut warnings, tokens[0].location(path), keyword);
+            }
+        }
+    }
+
+    let mut obj_data = ObjData::new(path.map(str::to_owned), verts, tex_coords, normals, faces);
+    obj_data.vert_colors = colors;
+    obj_data.vert_weights = weights;
+    obj_data.lines = lines;
+    obj_data.points = points;
+    obj_data.skipped_faces = skipped_faces;
+    obj_data.material_libs = material_libs;
+    obj_data.material_names = material_names;
+    obj_data.objects = objects;
+    obj_data.groups = groups;
+    obj_data.freeform = freeform.geometry;
+    obj_data.warnings = warnings;
+    Ok(obj_data)
+}
+
+/// Joins the name tokens of a statement and returns its index in `names`,
+/// adding it if it hasn't been seen before.
+fn intern(names:&mut Vec<String>, tokens:&[Token])->usize{
+    let name = tokens.iter().map(|t| t.text).collect::<Vec<_>>().join(" ");
+    match names.iter().position(|n| *n == name){
+        Some(index) => index,
+        None => {
+            names.push(name);
+            names.len()-1
+        }
+    }
+}
+
+/// Records an unsupported statement, counted per keyword.
+fn unsupported(warnings:&mut Vec<Warning>, loc:Location, keyword:&str){
+    let existing = warnings.iter_mut().find_map(|warning| match warning{
+        Warning::Unsupported { keyword:k, count, .. } if k == keyword => Some(count),
+        _ => None
+    });
+    match existing{
+        Some(count) => *count += 1,
+        None => warnings.push(Warning::Unsupported { loc, keyword:keyword.to_string(), count:1 })
+    }
+}
+
+/// Makes sure every slot of a face corner refers to an element that was
+/// actually parsed.
+fn check_face(obj_data:&ObjData, face:&Face)->Result<(), ObjError>{
+    for (corner, fi) in face.indices.iter().enumerate(){
+        let slots = [
+            (Slot::Position, Some(fi.pos), obj_data.vert_positions.len()),
+            (Slot::TexCoord, fi.tex, obj_data.tex_coords.len()),
+            (Slot::Normal, fi.norm, obj_data.normals.len())
+        ];
+        for (slot, index, len) in slots{
+            if let Some(index) = index && index >= len{
+                return Err(ObjError::OutOfRange {
+                    loc:face.location(obj_data.path.as_deref(), corner),
+                    slot,
+                    index:index as i64 + 1,
+                    len
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the free-form elements only refer to elements that exist, which
+/// the parser makes sure of but caller built `ObjData` may not. Curves and
+/// surfaces are left out as `invalid_faces` says, a bad trimming curve always
+/// fails as surfaces refer to trimming curves by index.
+fn check_freeform(obj_data:&ObjData, freeform:FreeformGeometry, invalid_faces:InvalidFaces, skipped_faces:&mut Vec<ObjError>)->Result<FreeformGeometry, ObjError>{
+    let lens = [obj_data.vert_positions.len(), obj_data.tex_coords.len(), obj_data.normals.len(), freeform.parameter_vertices.len(), freeform.curves2.len()];
+    let check = |line:usize, column:usize, references:Vec<(Slot, usize)>|->Result<(), ObjError>{
+        for (slot, index) in references{
+            let len = match slot{
+                Slot::Position => lens[0],
+                Slot::TexCoord => lens[1],
+                Slot::Normal => lens[2],
+                Slot::ParameterVertex => lens[3],
+                Slot::Curve2 => lens[4]
+            };
+            if index >= len{
+                return Err(ObjError::OutOfRange {
+                    loc:Location::new(obj_data.path.as_deref(), line, column),
+                    slot,
+                    index:index as i64 + 1,
+                    len
+                });
+            }
+        }
+        Ok(())
+    };
+    let mut keep = |result:Result<(), ObjError>|->Result<bool, ObjError>{
+        match result{
+            Ok(()) => Ok(true),
+            Err(err) if invalid_faces == InvalidFaces::Skip => {
+                skipped_faces.push(err);
+                Ok(false)
+            }
+            Err(err) => Err(err)
+        }
+    };
+
+    for curve in &freeform.curves2{
+        check(curve.line, 0, curve.control_points.iter().map(|i| (Slot::ParameterVertex, *i)).collect())?;
+    }
+    let mut curves = Vec::with_capacity(freeform.curves.len());
+    for curve in freeform.curves{
+        let references = curve.control_points.iter().map(|i| (Slot::Position, *i))
+            .chain(curve.special_points.iter().map(|i| (Slot::ParameterVertex, *i)));
+        if keep(check(curve.line, curve.column, references.collect()))?{
+            curves.push(curve);
+        }
+    }
+    let mut surfaces = Vec::with_capacity(freeform.surfaces.len());
+    for surface in freeform.surfaces{
+        let control_points = surface.control_points.iter().flat_map(|cp|{
+            [(Slot::Position, Some(cp.position)), (Slot::TexCoord, cp.tex_coord), (Slot::Normal, cp.normal)]
+                .into_iter().filter_map(|(slot, index)| Some((slot, index?)))
+        });
+        let loops = surface.trims.iter().chain(&surface.holes).chain(&surface.special_curves).flatten()
+            .map(|curve| (Slot::Curve2, curve.curve));
+        let special_points = surface.special_points.iter().map(|i| (Slot::ParameterVertex, *i));
+        if keep(check(surface.line, surface.column, control_points.chain(loops).chain(special_points).collect()))?{
+            surfaces.push(surface);
+        }
+    }
+    Ok(FreeformGeometry { curves, surfaces, ..freeform })
+}
+
+/// Gives every corner of `face` that is missing a tex coord or normal one,
+/// appending any new values to `obj_data`. `default_slots` caches where the
+/// configured default values were stored so they're only added once.
+fn fill_missing(obj_data:&mut ObjData, face:&mut Face, contains:[bool;2], default_slots:&mut [Option<usize>;2], options:&LoadOptions)->Result<(), ObjError>{
+    let [contains_tex_coords, contains_normals] = contains;
+    let missing_tex = contains_tex_coords && face.indices.iter().any(|fi| fi.tex.is_none());
+    let missing_norm = contains_normals && face.indices.iter().any(|fi| fi.norm.is_none());
+    if !missing_tex && !missing_norm{
+        return Ok(());
+    }
+
+    let missing_error = |slot:Slot, face:&Face|{
+        let corner = face.indices.iter().position(|fi| match slot{
+            Slot::TexCoord => fi.tex.is_none(),
+            _ => fi.norm.is_none()
+        }).unwrap_or(0);
+        ObjError::MissingAttribute {
+            loc:face.location(obj_data.path.as_deref(), corner),
+            slot
+        }
+    };
+    let points:Vec<V3> = face.indices.iter().map(|fi| v3(&obj_data.vert_positions[fi.pos])).collect();
+    let face_normal = normalize(polygon_normal(&points));
+
+    // TEX COORDS
+    if missing_tex{
+        let mut new_tex:Vec<Option<usize>> = vec![None; face.indices.len()];
+        match options.missing_tex_coords{
+            MissingAttribute::Error => return Err(missing_error(Slot::TexCoord, face)),
+            MissingAttribute::Default([u, v]) => {
+                let slot = *default_slots[0].get_or_insert_with(|| {
+                    obj_data.tex_coords.push(Vec3::new(u, v, 0.));
+                    obj_data.tex_coords.len()-1
+                });
+                new_tex.fill(Some(slot));
+            }
+            // Planar projection along the face's dominant axis
+            MissingAttribute::Generate => {
+                let axis = dominant_axis(face_normal);
+                for (tex, p) in new_tex.iter_mut().zip(&points){
+                    obj_data.tex_coords.push(Vec3::new(p[(axis+1) % 3], p[(axis+2) % 3], 0.));
+                    *tex = Some(obj_data.tex_coords.len()-1);
                 }
-                face_indices.push(face_ind);
             }
-            faces.push(face_indices);
+        }
+        for (fi, tex) in face.indices.iter_mut().zip(new_tex){
+            fi.tex = fi.tex.or(tex);
         }
     }
 
-    Ok(ObjData::new(verts,tex_coords,normals,faces))
+    // NORMALS
+    if missing_norm{
+        let slot = match options.missing_normals{
+            MissingAttribute::Error => return Err(missing_error(Slot::Normal, face)),
+            MissingAttribute::Default([x, y, z]) => *default_slots[1].get_or_insert_with(|| {
+                obj_data.normals.push(Vec3::new(x, y, z));
+                obj_data.normals.len()-1
+            }),
+            MissingAttribute::Generate => {
+                obj_data.normals.push(Vec3::new(face_normal[0], face_normal[1], face_normal[2]));
+                obj_data.normals.len()-1
+            }
+        };
+        for fi in &mut face.indices{
+            fi.norm = fi.norm.or(Some(slot));
+        }
+    }
+    Ok(())
 }
 
-fn index_data(obj_data:ObjData, contains_tex_coords:bool, contains_normals:bool)->(Vec<u32>, Vec<f32>){
+/// Reads every `mtllib` the obj references, resolving them against
+/// `base_dir`. Libraries that don't exist are skipped with a warning so a
+/// missing mtl doesn't stop the geometry from loading.
+fn load_material_libs(obj_data:&mut ObjData, base_dir:&Path)->Result<Vec<Material>, ObjError>{
+    let mut materials = Vec::new();
+    for (lib, loc) in &obj_data.material_libs{
+        let lib_path = base_dir.join(lib);
+        match load_mtl(&lib_path, base_dir){
+            Ok(lib_materials) => materials.extend(lib_materials),
+            Err(ObjError::Io { source, .. }) if source.kind() == ErrorKind::NotFound =>
+                obj_data.warnings.push(Warning::MissingMaterialLib { loc:loc.clone(), path:lib_path }),
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(materials)
+}
+
+/// Tessellates the free-form curves and surfaces, adding the results as
+/// lines and faces so they're indexed along with the rest of the obj.
+fn tessellate_freeform(obj_data:&mut ObjData, tolerance:f64){
+    let freeform = std::mem::take(&mut obj_data.freeform);
+    let path = obj_data.path.clone();
+    let untessellated = |line:usize, column:usize, keyword:&str| Warning::Untessellated {
+        loc:Location::new(path.as_deref(), line, column),
+        keyword:keyword.to_string()
+    };
+
+    for curve in &freeform.curves{
+        let points:Vec<Vec<f64>> = curve.control_points.iter().map(|&i| v3(&obj_data.vert_positions[i]).to_vec()).collect();
+        let weights:Vec<f64> = curve.control_points.iter().map(|&i| obj_data.vert_weights.get(i).copied().unwrap_or(1.)).collect();
+        let Some(polyline) = freeform::tessellate_curve(curve, &points, &weights, tolerance) else {
+            obj_data.warnings.push(untessellated(curve.line, curve.column, "curv"));
+            continue;
+        };
+        let start = obj_data.vert_positions.len();
+        obj_data.vert_positions.extend(polyline.iter().map(|p| Vec3::new(p[0], p[1], p[2])));
+        obj_data.lines.push(Face {
+            indices:(start..obj_data.vert_positions.len()).map(|pos| FaceIndex::new(pos, None, None)).collect(),
+            corners:vec![(curve.line, curve.column); polyline.len()],
+            material:None,
+            object:None,
+            group:None,
+            smoothing:0
+        });
+    }
+
+    for surface in &freeform.surfaces{
+        // Tex coords and normals are interpolated along with the positions
+        // when every control point has them
+        let control_points = &surface.control_points;
+        let has_tex = control_points.iter().all(|cp| cp.tex_coord.is_some());
+        let has_norm = control_points.iter().all(|cp| cp.normal.is_some());
+        let points:Vec<Vec<f64>> = control_points.iter().map(|cp|{
+            let mut point = v3(&obj_data.vert_positions[cp.position]).to_vec();
+            if let Some(tex) = cp.tex_coord && has_tex{
+                let tex = &obj_data.tex_coords[tex];
+                point.extend([tex.x, tex.y, tex.z]);
+            }
+            if let Some(norm) = cp.normal && has_norm{
+                point.extend(v3(&obj_data.normals[norm]));
+            }
+            point
+        }).collect();
+        let weights:Vec<f64> = control_points.iter().map(|cp| obj_data.vert_weights.get(cp.position).copied().unwrap_or(1.)).collect();
+        let Some(mesh) = freeform::tessellate_surface(surface, &points, &weights, &freeform, tolerance) else {
+            obj_data.warnings.push(untessellated(surface.line, surface.column, "surf"));
+            continue;
+        };
+
+        let starts = [obj_data.vert_positions.len(), obj_data.tex_coords.len(), obj_data.normals.len()];
+        for (point, normal) in mesh.points.iter().zip(&mesh.normals){
+            obj_data.vert_positions.push(Vec3::new(point[0], point[1], point[2]));
+            let mut rest = &point[3..];
+            if has_tex{
+                obj_data.tex_coords.push(Vec3::new(rest[0], rest[1], rest[2]));
+                rest = &rest[3..];
+            }
+            let normal = if has_norm { normalize([rest[0], rest[1], rest[2]]) } else { *normal };
+            obj_data.normals.push(Vec3::new(normal[0], normal[1], normal[2]));
+        }
+        for triangle in mesh.triangles{
+            obj_data.faces.push(Face {
+                indices:triangle.iter().map(|i| FaceIndex::new(starts[0] + i, has_tex.then_some(starts[1] + i), Some(starts[2] + i))).collect(),
+                corners:vec![(surface.line, surface.column); 3],
+                material:surface.material,
+                object:surface.object,
+                group:surface.group,
+                smoothing:surface.smoothing
+            });
+        }
+    }
+    obj_data.freeform = freeform;
+}
+
+fn index_data(mut obj_data:ObjData, mut materials:Vec<Material>, options:&LoadOptions)->Result<ObjLoader, ObjError>{
     let mut indices:Vec<u32> = Vec::new();
-    let mut obj_map: HashMap<FaceIndex, ObjObject> = HashMap::new();
     let mut data_vec:Vec<ObjObject> = Vec::new();
 
-    let mut check_index = |fi:&FaceIndex| {
-        let objobj = obj_map.get(fi);
-        let obj_index:usize;
-        if objobj == None{
-            let mut new_obj = ObjObject::new(
-                data_vec.len(),
-                &obj_data.vert_positions[fi.pos as usize],
-            );
-            if contains_tex_coords{
-                new_obj.tex_coord = obj_data.tex_coords[fi.tex as usize].clone();
+    // REBASE POSITIONS, before anything is worked out from them so far off
+    // geometry keeps its precision
+    let origin = match options.origin{
+        Some(Origin::Supplied(origin)) => origin,
+        Some(Origin::Computed) if !obj_data.vert_positions.is_empty() => {
+            let mut min = [f64::INFINITY;3];
+            let mut max = [f64::NEG_INFINITY;3];
+            for p in obj_data.vert_positions.iter().map(v3){
+                min.iter_mut().zip(p).for_each(|(m, c)| *m = m.min(c));
+                max.iter_mut().zip(p).for_each(|(m, c)| *m = m.max(c));
             }
-            if contains_normals{
-                new_obj.normal = obj_data.normals[fi.norm as usize].clone();
+            scale(add(min, max), 0.5)
+        }
+        _ => [0.;3]
+    };
+    if origin != [0.;3]{
+        for p in &mut obj_data.vert_positions{
+            p.x -= origin[0];
+            p.y -= origin[1];
+            p.z -= origin[2];
+        }
+    }
+
+    // Caller built lines and points may still have normals, they're never
+    // output so aren't worth validating
+    for fi in obj_data.lines.iter_mut().chain(&mut obj_data.points).flat_map(|e| &mut e.indices){
+        fi.norm = None;
+    }
+
+    // VALIDATE FACE REFERENCES
+    let mut skipped_faces = std::mem::take(&mut obj_data.skipped_faces);
+    let elements = [&mut obj_data.faces, &mut obj_data.lines, &mut obj_data.points].map(std::mem::take);
+    let mut validate = |elements:Vec<Face>|->Result<Vec<Face>, ObjError>{
+        let mut valid = Vec::with_capacity(elements.len());
+        for face in elements{
+            match check_face(&obj_data, &face){
+                Ok(()) => valid.push(face),
+                Err(err) if options.invalid_faces == InvalidFaces::Skip => skipped_faces.push(err),
+                Err(err) => return Err(err)
             }
-            obj_map.insert(fi.clone(), new_obj.clone());
-            data_vec.push(new_obj.clone());
-            obj_index = new_obj.index;
         }
-        else{
-            obj_index = objobj.unwrap().index;
+        Ok(valid)
+    };
+    let [faces, lines, points] = elements;
+    let faces = validate(faces)?;
+    let lines = validate(lines)?;
+    let points = validate(points)?;
+    let freeform = std::mem::take(&mut obj_data.freeform);
+    obj_data.freeform = check_freeform(&obj_data, freeform, options.invalid_faces, &mut skipped_faces)?;
+    skipped_faces.sort_by_key(|err| err.location().line);
+
+    // Tessellated elements are appended after the validated ones, their
+    // references are always in range
+    [obj_data.faces, obj_data.lines, obj_data.points] = [faces, lines, points];
+    if let Some(tolerance) = options.tessellate_freeform{
+        tessellate_freeform(&mut obj_data, tolerance);
+    }
+    let [mut faces, lines, points] = [&mut obj_data.faces, &mut obj_data.lines, &mut obj_data.points].map(std::mem::take);
+
+    // Attributes are only output if some face actually uses them, corners
+    // that leave them out are filled according to the options. Lines and
+    // points can bring tex coords but have no normals to fill.
+    let contains_tex_coords = faces.iter().chain(&lines).chain(&points).flat_map(|f| &f.indices).any(|fi| fi.tex.is_some());
+    let mut contains_normals = faces.iter().flat_map(|f| &f.indices).any(|fi| fi.norm.is_some());
+
+    // GENERATE NORMALS
+    if let Some(generate) = &options.generate_normals && (generate.replace_existing || !contains_normals){
+        let positions:Vec<V3> = obj_data.vert_positions.iter().map(v3).collect();
+        let corners:Vec<Vec<usize>> = faces.iter().map(|f| f.indices.iter().map(|fi| fi.pos).collect()).collect();
+        let generated = match generate.crease_angle{
+            Some(angle) => normals::crease_angle_normals(&positions, &corners, angle, generate.weighting),
+            None => {
+                let groups:Vec<u32> = faces.iter().map(|f| f.smoothing).collect();
+                normals::smoothing_group_normals(&positions, &corners, &groups, generate.weighting)
+            }
+        };
+        obj_data.normals = generated.normals.iter().map(|n| Vec3::new(n[0], n[1], n[2])).collect();
+        for (face, normals) in faces.iter_mut().zip(generated.corners){
+            for (fi, norm) in face.indices.iter_mut().zip(normals){
+                fi.norm = Some(norm);
+            }
+        }
+        contains_normals = !faces.is_empty();
+    }
+    let mut default_slots = [None, None];
+    for face in &mut faces{
+        fill_missing(&mut obj_data, face, [contains_tex_coords, contains_normals], &mut default_slots, options)?;
+    }
+
+    let contains_tangents = options.generate_tangents && contains_tex_coords && contains_normals;
+
+    // TRIANGULATE, up front so tangents come from the triangles actually
+    // output. Faces output whole get none, unless they're polygons too big
+    // for the tangent generator. Triangles and quads are always fanned, ear
+    // clipping only pays off for larger polygons.
+    let keep_whole = |corners:usize| match options.primitive{
+        Primitive::Polygons => true,
+        Primitive::Quads(_) => corners == 4,
+        Primitive::Triangles => false
+    };
+    let face_triangles:Vec<Vec<[usize;3]>> = faces.iter().map(|face|{
+        let corners = face.indices.len();
+        if keep_whole(corners) && (corners <= 4 || !contains_tangents){
+            return Vec::new();
         }
+        match options.triangulation{
+            Triangulation::EarClipping if corners > 4 => {
+                let points:Vec<V3> = face.indices.iter().map(|fi| v3(&obj_data.vert_positions[fi.pos])).collect();
+                triangulate::ear_clip(&points)
+            }
+            _ => triangulate::fan(corners)
+        }
+    }).collect();
+
+    // GENERATE TANGENTS, needs both tex coords and normals to work from.
+    // `face_tangents` holds a tangent per corner for faces output whole, and
+    // per triangle corner for the rest.
+    let mut face_tangents:Vec<Vec<usize>> = Vec::new();
+    let mut tangents:Vec<[f64;4]> = Vec::new();
+    if contains_tangents{
+        let mut tangent_faces:Vec<tangents::TangentFace> = Vec::new();
+        for (face, triangles) in faces.iter().zip(&face_triangles){
+            let whole:Vec<usize> = (0..face.indices.len()).collect();
+            let primitives:Vec<&[usize]> = match triangles.is_empty(){
+                true => vec![&whole],
+                false => triangles.iter().map(|t| t.as_slice()).collect()
+            };
+            for primitive in primitives{
+                let mut tangent_face = tangents::TangentFace { positions:Vec::new(), tex_coords:Vec::new(), normals:Vec::new() };
+                for fi in primitive.iter().map(|corner| &face.indices[*corner]){
+                    // Missing slots were all filled in above
+                    let tex = &obj_data.tex_coords[fi.tex.unwrap_or(0)];
+                    let norm = &obj_data.normals[fi.norm.unwrap_or(0)];
+                    tangent_face.positions.push(v3(&obj_data.vert_positions[fi.pos]));
+                    tangent_face.tex_coords.push([tex.x, tex.y]);
+                    tangent_face.normals.push(normalize(v3(norm)));
+                }
+                tangent_faces.push(tangent_face);
+            }
+        }
+        let generated = tangents::generate_tangents(&tangent_faces);
+        tangents = generated.tangents;
+
+        let mut generated_corners = generated.corners.into_iter();
+        face_tangents = faces.iter().zip(&face_triangles).map(|(face, triangles)|{
+            if triangles.is_empty(){
+                return generated_corners.next().unwrap_or_default();
+            }
+            let slots:Vec<usize> = generated_corners.by_ref().take(triangles.len()).flatten().collect();
+            if !keep_whole(face.indices.len()){
+                return slots;
+            }
+            // A large polygon output whole takes each corner's tangent from
+            // the first triangle that uses it
+            let order:Vec<usize> = triangles.iter().flatten().copied().collect();
+            (0..face.indices.len()).map(|corner| order.iter().position(|c| *c == corner).map_or(0, |slot| slots[slot])).collect()
+        }).collect();
+    }
+
+    // Vertices are deduplicated on their face index, plus the tangent frame
+    // they were given since mirrored UVs can split otherwise equal corners.
+    let mut obj_map: HashMap<(FaceIndex, Option<usize>), ObjObject> = HashMap::new();
+    let mut check_index = |fi:&FaceIndex, tangent:Option<usize>| {
+        let key = (fi.clone(), tangent);
+        let obj_index = match obj_map.get(&key){
+            Some(objobj) => objobj.index,
+            None => {
+                let mut new_obj = ObjObject::new(
+                    data_vec.len(),
+                    &obj_data.vert_positions[fi.pos],
+                );
+                if let Some(tex) = fi.tex && contains_tex_coords{
+                    new_obj.tex_coord = obj_data.tex_coords[tex].clone();
+                }
+                if let Some(norm) = fi.norm && contains_normals{
+                    new_obj.normal = obj_data.normals[norm].clone();
+                }
+                if let Some(tangent) = tangent{
+                    new_obj.tangent = tangents[tangent];
+                }
+                if let Some(color) = obj_data.vert_colors.get(fi.pos){
+                    new_obj.color = *color;
+                }
+                obj_map.insert(key, new_obj.clone());
+                data_vec.push(new_obj.clone());
+                new_obj.index
+            }
+        };
         obj_index as u32
     };
 
-    for face in &obj_data.faces{
-        let mut face_c = face.clone();
+    // Map `usemtl` names onto the loaded materials, any that no library
+    // defines get a blank material so every range still has a name.
+    let material_map:Vec<usize> = obj_data.material_names.iter().map(|name|{
+        materials.iter().position(|m| m.name == *name).unwrap_or_else(|| {
+            materials.push(Material::new(name));
+            materials.len()-1
+        })
+    }).collect();
+    let mut material_ranges:Vec<MaterialRange> = Vec::new();
 
-        let first_ind = check_index(&face_c[0]);
-        face_c.remove(0);
-        while face_c.len() >= 2{
-            indices.push(first_ind);
-            indices.push(check_index(&face_c[0]));
-            indices.push(check_index(&face_c[1]));
-            face_c.remove(0);
-        }
+    // SPLIT INTO MESHES: faces are bucketed by whichever of object, group and
+    // material the options split on, buckets are kept in order of first use.
+    let split = options.split;
+    let mut mesh_keys:HashMap<MeshKey, usize> = HashMap::new();
+    let mut mesh_faces:Vec<Vec<usize>> = Vec::new();
+    let mut meshes:Vec<Mesh> = Vec::new();
+    for (face_index, face) in faces.iter().enumerate(){
+        let key = (
+            face.object.filter(|_| split.objects),
+            face.group.filter(|_| split.groups),
+            face.material.map(|m| material_map[m]).filter(|_| split.materials)
+        );
+        let mesh = *mesh_keys.entry(key).or_insert_with(|| {
+            let (object, group, material) = key;
+            let object = object.map(|o| obj_data.objects[o].clone());
+            let group = group.map(|g| obj_data.groups[g].clone());
+            let name = [object.as_deref(), group.as_deref(), material.map(|m| materials[m].name.as_str())]
+                .into_iter().flatten().collect::<Vec<_>>().join("/");
+            meshes.push(Mesh { name, object, group, material, start:0, count:0, face_start:0, face_count:0, leftover_start:0, leftover_count:0 });
+            mesh_faces.push(Vec::new());
+            meshes.len()-1
+        });
+        mesh_faces[mesh].push(face_index);
     }
 
-    let mut raw_data = Vec::new();
-    for chunk in &data_vec{
-        raw_data.push(chunk.pos.x as f32);
-        raw_data.push(chunk.pos.y as f32);
-        raw_data.push(chunk.pos.z as f32);
-        if contains_tex_coords{
-            raw_data.push(chunk.tex_coord.x as f32);
-            raw_data.push(chunk.tex_coord.y as f32);
+    let mut face_sizes:Vec<u32> = Vec::new();
+    let mut leftover_triangles:Vec<u32> = Vec::new();
+    for (mesh, mesh_faces) in meshes.iter_mut().zip(mesh_faces){
+        mesh.start = indices.len();
+        mesh.face_start = face_sizes.len();
+        mesh.leftover_start = leftover_triangles.len();
+        for face_index in mesh_faces{
+            let face = &faces[face_index];
+            let tangent = |slot:usize| face_tangents.get(face_index).map(|t| t[slot]);
+            let material = face.material.map(|m| material_map[m]);
+            match material_ranges.last_mut(){
+                Some(range) if range.material == material => {}
+                _ => material_ranges.push(MaterialRange {
+                    material,
+                    start:indices.len(),
+                    count:0,
+                    leftover_start:leftover_triangles.len(),
+                    leftover_count:0
+                })
+            }
+
+            let corners = face.indices.len();
+            if keep_whole(corners){
+                for (corner, fi) in face.indices.iter().enumerate(){
+                    let index = check_index(fi, tangent(corner));
+                    indices.push(index);
+                }
+                if options.primitive == Primitive::Polygons{
+                    face_sizes.push(corners as u32);
+                }
+                continue;
+            }
+
+            let (target, pad) = match options.primitive{
+                Primitive::Quads(NonQuads::Error) => return Err(ObjError::NotAQuad {
+                    loc:face.location(obj_data.path.as_deref(), 0),
+                    corners
+                }),
+                Primitive::Quads(NonQuads::Triangulate) => (&mut leftover_triangles, false),
+                Primitive::Quads(NonQuads::Degenerate) => (&mut indices, true),
+                _ => (&mut indices, false)
+            };
+            for (t, triangle) in face_triangles[face_index].iter().enumerate(){
+                for (k, corner) in triangle.iter().enumerate(){
+                    let index = check_index(&face.indices[*corner], tangent(t*3 + k));
+                    target.push(index);
+                }
+                // Repeat the last corner to make a quad with a collapsed edge
+                if pad{
+                    let last = target[target.len()-1];
+                    target.push(last);
+                }
+            }
         }
-        if contains_normals{
-            raw_data.push(chunk.normal.x as f32);
-            raw_data.push(chunk.normal.y as f32);
-            raw_data.push(chunk.normal.z as f32);
+        mesh.count = indices.len() - mesh.start;
+        mesh.face_count = face_sizes.len() - mesh.face_start;
+        mesh.leftover_count = leftover_triangles.len() - mesh.leftover_start;
+    }
+
+    // LINES AND POINTS share vertices with the faces
+    let mut line_indices:Vec<u32> = Vec::new();
+    for line in &lines{
+        for fi in line.indices.windows(2).flatten(){
+            line_indices.push(check_index(fi, None));
         }
     }
+    let point_indices:Vec<u32> = points.iter()
+        .flat_map(|point| &point.indices)
+        .map(|fi| check_index(fi, None))
+        .collect();
+
+    let (mut end, mut leftover_end) = (indices.len(), leftover_triangles.len());
+    for range in material_ranges.iter_mut().rev(){
+        range.count = end - range.start;
+        range.leftover_count = leftover_end - range.leftover_start;
+        end = range.start;
+        leftover_end = range.leftover_start;
+    }
+
+    let mut loader = ObjLoader {
+        indices,
+        face_sizes,
+        leftover_triangles,
+        line_indices,
+        point_indices,
+        vert_data:Vec::new(),
+        contains_tex_coords,
+        tex_coord_w:options.tex_coord_w,
+        contains_normals,
+        contains_tangents,
+        contains_colors:!obj_data.vert_colors.is_empty(),
+        skipped_faces,
+        warnings:std::mem::take(&mut obj_data.warnings),
+        freeform:std::mem::take(&mut obj_data.freeform),
+        primitive:options.primitive,
+        origin,
+        materials,
+        material_ranges,
+        meshes,
+        vertices:data_vec
+    };
+
+    loader.vert_data = loader.interleaved(|v| v as f32);
 
-    (indices, raw_data)
+    Ok(loader)
 } 
 
 impl ObjLoader{
-    pub fn from_file(file_loc:&str)->Self{
-        let obj_data = obj_get_data(file_loc).unwrap();
-        let mut contains_tex_coords = false;
-        let mut contains_normals = false;
-        if obj_data.tex_coords.len() > 0{
-            contains_tex_coords = true;
-        }
-        if obj_data.normals.len() > 0{
-            contains_normals = true;
-        }
-        let (indices, vert_data ) = index_data(obj_data, contains_tex_coords, contains_normals);
-        ObjLoader { indices, vert_data, contains_tex_coords,contains_normals }
+    /// Loads and indexes the obj file at `file_loc`.
+    pub fn from_file(file_loc:&str)->Result<Self, ObjError>{
+        Self::from_file_with(file_loc, &LoadOptions::default())
+    }
+
+    pub fn from_file_with(file_loc:&str, options:&LoadOptions)->Result<Self, ObjError>{
+        let file = File::open(file_loc)
+            .map_err(|source| ObjError::Io { loc:Location::new(Some(file_loc), 0, 0), source })?;
+        Self::load(BufReader::new(file), Some(file_loc), options)
+    }
+
+    /// Loads and indexes obj data from any buffered reader, eg. an archive
+    /// entry or a network stream.
+    pub fn from_reader<R:BufRead>(reader:R)->Result<Self, ObjError>{
+        Self::from_reader_with(reader, &LoadOptions::default())
+    }
+
+    pub fn from_reader_with<R:BufRead>(reader:R, options:&LoadOptions)->Result<Self, ObjError>{
+        Self::load(reader, None, options)
+    }
+
+    /// Same as `str::parse`, but with non-default options.
+    pub fn from_str_with(s:&str, options:&LoadOptions)->Result<Self, ObjError>{
+        Self::from_reader_with(s.as_bytes(), options)
+    }
+
+    fn load<R:BufRead>(reader:R, path:Option<&str>, options:&LoadOptions)->Result<Self, ObjError>{
+        Self::from_data(obj_get_data(reader, path, options)?, options)
+    }
+
+    /// Indexes parsed obj data, loading its materials first.
+    pub fn from_data(mut obj_data:ObjData, options:&LoadOptions)->Result<Self, ObjError>{
+        // Libraries are looked up next to the obj, or in `material_dir`
+        // when there's no file to be next to.
+        let base_dir = match &obj_data.path{
+            Some(path) => Path::new(path).parent().map(Path::to_path_buf),
+            None => options.material_dir.clone()
+        };
+        let materials = match &base_dir{
+            Some(base_dir) if options.load_materials => load_material_libs(&mut obj_data, base_dir)?,
+            _ => Vec::new()
+        };
+        index_data(obj_data, materials, options)
     }
     
     pub fn contains_tex_coords(&self)->bool{
@@ -202,9 +1023,380 @@ impl ObjLoader{
         self.contains_normals
     }
 
+    pub fn contains_tangents(&self)->bool{
+        self.contains_tangents
+    }
+
+    pub fn contains_colors(&self)->bool{
+        self.contains_colors
+    }
+
+    /// Faces, lines and points that were dropped because they referenced
+    /// missing elements, only populated when loading with `InvalidFaces::Skip`.
+    pub fn skipped_faces(&self)->&[ObjError]{
+        &self.skipped_faces
+    }
+
+    /// Statements that were ignored, followed by any mtl libraries that
+    /// couldn't be found.
+    pub fn warnings(&self)->&[Warning]{
+        &self.warnings
+    }
+
+    /// The free-form curves and surfaces as parsed, whether or not they were
+    /// tessellated.
+    pub fn freeform(&self)->&FreeformGeometry{
+        &self.freeform
+    }
+
+    /// Every material from the obj's mtl libraries, plus blank ones for any
+    /// `usemtl` names the libraries don't define.
+    pub fn materials(&self)->&[Material]{
+        &self.materials
+    }
+
+    /// Consecutive runs of `indices` drawn with the same material, in order.
+    pub fn material_ranges(&self)->&[MaterialRange]{
+        &self.material_ranges
+    }
+
+    /// The meshes the faces were split into according to `LoadOptions::split`,
+    /// each covering one contiguous range of `indices`.
+    pub fn meshes(&self)->&[Mesh]{
+        &self.meshes
+    }
+
+    /// Copies a mesh out into its own vertex and index buffers, keeping only
+    /// the vertices it uses. Returned in the same order as `get_data`.
+    pub fn mesh_data(&self, mesh:&Mesh)->(Vec<f32>, Vec<u32>){
+        let stride = self.vertex_info().stride;
+        let mut remap:HashMap<u32, u32> = HashMap::new();
+        let mut vert_data = Vec::new();
+        let mut indices = Vec::with_capacity(mesh.count);
+        for &index in &self.indices[mesh.start..mesh.start+mesh.count]{
+            let new_index = *remap.entry(index).or_insert_with(|| {
+                let start = index as usize * stride;
+                vert_data.extend_from_slice(&self.vert_data[start..start+stride]);
+                (vert_data.len() / stride - 1) as u32
+            });
+            indices.push(new_index);
+        }
+        (vert_data, indices)
+    }
+
+    /// What `indices` holds, as set by `LoadOptions::primitive`.
+    pub fn primitive(&self)->Primitive{
+        self.primitive
+    }
+
+    /// The attributes the loaded vertices have data for.
+    pub fn available_attributes(&self)->Vec<Attribute>{
+        let mut attributes = vec![Attribute::Position];
+        if self.contains_tex_coords{
+            attributes.push(if self.tex_coord_w { Attribute::TexCoord3 } else { Attribute::TexCoord });
+        }
+        if self.contains_normals{
+            attributes.push(Attribute::Normal);
+        }
+        if self.contains_tangents{
+            attributes.push(Attribute::Tangent);
+        }
+        if self.contains_colors{
+            attributes.push(Attribute::Color);
+        }
+        attributes
+    }
+
+    /// Vertex count, stride and attribute offsets of `vert_data`.
+    pub fn vertex_info(&self)->VertexInfo{
+        VertexInfo::new(self.vertices.len(), &self.available_attributes())
+    }
+
+    /// Packs the vertices into buffers laid out as `layout` describes, to be
+    /// used with `indices`.
+    pub fn pack(&self, layout:&VertexLayout)->VertexBuffers{
+        layout::pack(&self.vertices, &self.available_attributes(), layout)
+    }
+
+    /// What was subtracted from every position, see `LoadOptions::origin`.
+    /// Zero when the positions weren't rebased.
+    pub fn origin(&self)->[f64;3]{
+        self.origin
+    }
+
+    /// The same data as `vert_data` at full precision, laid out as
+    /// `vertex_info` describes.
+    pub fn vert_data_f64(&self)->Vec<f64>{
+        self.interleaved(|v| v)
+    }
+
+    /// Interleaves the vertices, matching `VertexLayout::default`.
+    fn interleaved<T>(&self, convert:fn(f64)->T)->Vec<T>{
+        let available = self.available_attributes();
+        let mut data = Vec::new();
+        for vertex in &self.vertices{
+            for attribute in &available{
+                let values = vertex.attribute(*attribute);
+                data.extend(values[..attribute.components()].iter().map(|v| convert(*v)));
+            }
+        }
+        data
+    }
+
     /// Gets the vertex data and indices from the loader. Passes ownership
     /// of the data, consuming the loader in the process. 
     pub fn get_data(self)->(Vec<f32>, Vec<u32>){
         (self.vert_data, self.indices)
     }
-}
\ No newline at end of file
+
+    /// `get_data`, with the vertex data at full precision.
+    pub fn get_data_f64(self)->(Vec<f64>, Vec<u32>){
+        (self.vert_data_f64(), self.indices)
+    }
+}
+
+/// Parses obj data held in memory, eg. from `include_str!`.
+impl FromStr for ObjLoader{
+    type Err = ObjError;
+
+    fn from_str(s:&str)->Result<Self, ObjError>{
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn positions(face:&Face)->Vec<usize>{
+        face.indices.iter().map(|fi| fi.pos).collect()
+    }
+
+    #[test]
+    fn mixed_positive_and_negative_indices(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nvt 1 0\nvn 0 0 1\n\
+            f 1 2 3\nf -3 -2 -1\nf 1/-2/-1 -2/2/1 3/-1/-1\nv 0 1 0\nf -1 1 -2\n";
+        let data = ObjData::from_reader(src.as_bytes(), &LoadOptions::default()).unwrap();
+        let faces:Vec<Vec<usize>> = data.faces.iter().map(positions).collect();
+        assert_eq!(faces, [vec![0, 1, 2], vec![0, 1, 2], vec![0, 1, 2], vec![3, 0, 2]]);
+        assert_eq!(data.faces[2].indices, [FaceIndex::new(0, Some(0), Some(0)), FaceIndex::new(1, Some(1), Some(0)), FaceIndex::new(2, Some(1), Some(0))]);
+    }
+
+    #[test]
+    fn negative_index_before_its_element(){
+        let src = "v 0 0 0\nv 1 0 0\nf -1 -2 -3\nv 1 1 0\n";
+        match ObjData::from_reader(src.as_bytes(), &LoadOptions::default()){
+            Err(ObjError::OutOfRange { loc, slot:Slot::Position, index:-3, len:2 }) => assert_eq!((loc.line, loc.column), (3, 9)),
+            other => panic!("{:?}", other.err())
+        }
+    }
+
+    #[test]
+    fn vertex_colors(){
+        // The first vertex's color has to be kept even though nothing before
+        // it had one
+        let src = "v 0 0 0 1 0 0\nv 1 0 0\nv 1 1 0 0 0 1 0.5\nf 1 2 3\n";
+        let data = ObjData::from_reader(src.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(data.vert_colors, [[1., 0., 0., 1.], [1.;4], [0., 0., 1., 0.5]]);
+        assert!(data.vert_weights.is_empty());
+
+        let loader = ObjLoader::from_data(data, &LoadOptions::default()).unwrap();
+        let info = loader.vertex_info();
+        let color = info.offset(Attribute::Color).unwrap();
+        assert_eq!(loader.vert_data[color..color+4], [1., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn w_components(){
+        let src = "v 0 0 0 2\nv 1 0 0\nvt 0.5\nvt 0.5 0.25 0.75\nf 1/1 2/2 1/2\n";
+        let data = ObjData::from_reader(src.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(data.vert_weights, [2., 1.]);
+        assert!(data.vert_colors.is_empty());
+        assert_eq!(data.tex_coords, [Vec3::new(0.5, 0., 0.), Vec3::new(0.5, 0.25, 0.75)]);
+
+        let options = LoadOptions { tex_coord_w:true, ..Default::default() };
+        let loader = ObjLoader::from_data(data, &options).unwrap();
+        let info = loader.vertex_info();
+        let tex = info.offset(Attribute::TexCoord3).unwrap();
+        assert_eq!(loader.vert_data[info.stride + tex..][..3], [0.5, 0.25, 0.75]);
+    }
+
+    const MIXED:&str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvt 0.5 0.5\nvn 0 0 1\nf 1 2 4\nf 1/1/1 2/1/1 3/1/1\n";
+
+    /// Tex coord and normal of each vertex of the first face.
+    fn first_face(options:&LoadOptions)->Vec<[f32;5]>{
+        let loader = ObjLoader::from_str_with(MIXED, options).unwrap();
+        let info = loader.vertex_info();
+        let (tex, norm) = (info.offset(Attribute::TexCoord).unwrap(), info.offset(Attribute::Normal).unwrap());
+        loader.indices[..3].iter().map(|i|{
+            let v = &loader.vert_data[*i as usize * info.stride..];
+            [v[tex], v[tex+1], v[norm], v[norm+1], v[norm+2]]
+        }).collect()
+    }
+
+    #[test]
+    fn missing_attributes_default(){
+        let options = LoadOptions {
+            missing_tex_coords:MissingAttribute::Default([0.25, 0.75]),
+            missing_normals:MissingAttribute::Default([1., 0., 0.]),
+            ..Default::default()
+        };
+        assert_eq!(first_face(&options), [[0.25, 0.75, 1., 0., 0.]; 3]);
+    }
+
+    #[test]
+    fn missing_attributes_generate(){
+        // The face lies in the xz plane, tex coords are its z and x
+        let options = LoadOptions { missing_tex_coords:MissingAttribute::Generate, missing_normals:MissingAttribute::Generate, ..Default::default() };
+        assert_eq!(first_face(&options), [[0., 0., 0., -1., 0.], [0., 1., 0., -1., 0.], [1., 0., 0., -1., 0.]]);
+    }
+
+    #[test]
+    fn missing_attributes_error(){
+        let options = LoadOptions { missing_tex_coords:MissingAttribute::Error, ..Default::default() };
+        match ObjLoader::from_str_with(MIXED, &options){
+            Err(ObjError::MissingAttribute { loc, slot:Slot::TexCoord }) => assert_eq!((loc.line, loc.column), (7, 3)),
+            other => panic!("{:?}", other.err())
+        }
+        let options = LoadOptions { missing_normals:MissingAttribute::Error, ..Default::default() };
+        assert!(matches!(ObjLoader::from_str_with(MIXED, &options), Err(ObjError::MissingAttribute { slot:Slot::Normal, .. })));
+        // Nothing is missing when no face has the attribute
+        ObjLoader::from_str_with("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", &options).unwrap();
+    }
+
+    const PARTS:&str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+        o a\ng x\nusemtl m1\nf 1 2 3\no b\nf 1 3 4\ng y\nusemtl m2\nf 2 3 4\no a\ng x\nusemtl m1\nf 1 2 4\n";
+
+    fn split(split:SplitBy)->ObjLoader{
+        ObjLoader::from_str_with(PARTS, &LoadOptions { split, load_materials:false, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn split_meshes(){
+        let ranges = |loader:&ObjLoader| loader.meshes().iter().map(|m| (m.name.clone(), m.start, m.count)).collect::<Vec<_>>();
+        assert_eq!(ranges(&split(SplitBy::default())), [(String::new(), 0, 12)]);
+
+        // Meshes are in order of first use, with all their faces together
+        let objects = split(SplitBy { objects:true, ..Default::default() });
+        assert_eq!(ranges(&objects), [("a".to_string(), 0, 6), ("b".to_string(), 6, 6)]);
+        assert_eq!(objects.indices[..6], [0, 1, 2, 0, 1, 3]);
+
+        let all = split(SplitBy { objects:true, groups:true, materials:true });
+        assert_eq!(ranges(&all), [("a/x/m1".to_string(), 0, 6), ("b/x/m1".to_string(), 6, 3), ("b/y/m2".to_string(), 9, 3)]);
+        let mesh = &all.meshes()[2];
+        assert_eq!((mesh.object.as_deref(), mesh.group.as_deref(), mesh.material), (Some("b"), Some("y"), Some(1)));
+
+        let groups = split(SplitBy { groups:true, ..Default::default() });
+        assert_eq!(ranges(&groups), [("x".to_string(), 0, 9), ("y".to_string(), 9, 3)]);
+    }
+
+    #[test]
+    fn mesh_data(){
+        let loader = split(SplitBy { objects:true, ..Default::default() });
+        let (vert_data, indices) = loader.mesh_data(&loader.meshes()[1]);
+        assert_eq!(indices, [0, 1, 2, 3, 1, 2]);
+        assert_eq!(vert_data, [0., 0., 0., 1., 1., 0., 0., 1., 0., 1., 0., 0.]);
+    }
+
+    const QUADS:&str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0.5 2 0\nf 1 2 3 4\nf 1 2 3\nf 1 2 3 5 4\n";
+
+    fn quads(non_quads:NonQuads)->Result<ObjLoader, ObjError>{
+        ObjLoader::from_str_with(QUADS, &LoadOptions { primitive:Primitive::Quads(non_quads), ..Default::default() })
+    }
+
+    #[test]
+    fn quads_error(){
+        match quads(NonQuads::Error){
+            Err(ObjError::NotAQuad { loc, corners }) => assert_eq!((loc.line, corners), (7, 3)),
+            other => panic!("{:?}", other.err())
+        }
+    }
+
+    #[test]
+    fn quads_triangulate(){
+        let loader = quads(NonQuads::Triangulate).unwrap();
+        assert_eq!(loader.indices, [0, 1, 2, 3]);
+        // One triangle, then the pentagon's three
+        assert_eq!(loader.leftover_triangles.len(), 12);
+        assert_eq!(loader.leftover_triangles[..3], [0, 1, 2]);
+        assert_eq!(loader.meshes()[0].leftover_count, 12);
+    }
+
+    #[test]
+    fn quads_degenerate(){
+        let loader = quads(NonQuads::Degenerate).unwrap();
+        assert_eq!(loader.indices.len(), 20);
+        assert_eq!(loader.indices[..8], [0, 1, 2, 3, 0, 1, 2, 2]);
+        for quad in loader.indices[8..].chunks_exact(4){
+            assert_eq!(quad[2], quad[3]);
+        }
+        assert!(loader.leftover_triangles.is_empty());
+    }
+
+    #[test]
+    fn leftover_material_ranges(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nusemtl a\nf 1 2 3\nf 1 2 3 4\nusemtl b\nf 1 3 4\nf 2 3 4\n";
+        let options = LoadOptions { primitive:Primitive::Quads(NonQuads::Triangulate), load_materials:false, ..Default::default() };
+        let loader = ObjLoader::from_str_with(src, &options).unwrap();
+        let ranges:Vec<_> = loader.material_ranges().iter()
+            .map(|r| (loader.materials()[r.material.unwrap()].name.as_str(), r.start, r.count, r.leftover_start, r.leftover_count))
+            .collect();
+        assert_eq!(ranges, [("a", 0, 4, 0, 3), ("b", 4, 0, 3, 6)]);
+    }
+
+    #[test]
+    fn lines_and_points_have_no_normals(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nvn 0 0 1\nvn 0 0 1\nvn 0 0 1\nf 1 2 3\nl 1//3 2//3\np 1//2\np 3//9\n";
+        let data = ObjData::from_reader(src.as_bytes(), &LoadOptions::default()).unwrap();
+        assert!(data.lines.iter().chain(&data.points).flat_map(|e| &e.indices).all(|fi| fi.norm.is_none()));
+
+        let generate = GenerateNormals { replace_existing:true, ..Default::default() };
+        let options = LoadOptions { generate_normals:Some(generate), ..Default::default() };
+        let loader = ObjLoader::from_str_with(src, &options).unwrap();
+        assert_eq!(loader.line_indices.len(), 2);
+        assert_eq!(loader.point_indices.len(), 2);
+
+        // Nor do lines in caller built data
+        let mut data = ObjData::from_reader(src.as_bytes(), &LoadOptions::default()).unwrap();
+        data.lines[0].indices[0].norm = Some(7);
+        ObjLoader::from_data(data, &options).unwrap();
+    }
+
+    #[test]
+    fn tessellation_doesnt_hide_bad_references(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\ncstype bezier\ndeg 2\ncurv 0 1 1 2 3\nparm u 0 1\nend\nf 1 2 5\n";
+        for tessellate_freeform in [None, Some(0.01)]{
+            let options = LoadOptions { tessellate_freeform, ..Default::default() };
+            match ObjLoader::from_str_with(src, &options){
+                Err(ObjError::OutOfRange { loc, slot:Slot::Position, index:5, len:3 }) => assert_eq!(loc.line, 9),
+                other => panic!("{:?}", other.err())
+            }
+            let options = LoadOptions { invalid_faces:InvalidFaces::Skip, ..options };
+            let loader = ObjLoader::from_str_with(src, &options).unwrap();
+            assert_eq!(loader.skipped_faces().len(), 1);
+            assert_eq!(loader.line_indices.is_empty(), tessellate_freeform.is_none());
+        }
+    }
+
+    #[test]
+    fn bad_freeform_references(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\ncstype bezier\ndeg 2\ncurv 0 1 1 2 3\nparm u 0 1\nend\nf 1 2 3\n";
+        let options = LoadOptions { tessellate_freeform:Some(0.01), ..Default::default() };
+        let data = ||{
+            let mut data = ObjData::from_reader(src.as_bytes(), &options).unwrap();
+            data.freeform.curves[0].control_points[2] = 9;
+            data
+        };
+        match ObjLoader::from_data(data(), &options){
+            Err(ObjError::OutOfRange { slot:Slot::Position, index:10, len:3, .. }) => {}
+            other => panic!("{:?}", other.err())
+        }
+
+        let skip = LoadOptions { invalid_faces:InvalidFaces::Skip, ..options.clone() };
+        let loader = ObjLoader::from_data(data(), &skip).unwrap();
+        assert_eq!(loader.skipped_faces().len(), 1);
+        assert!(loader.freeform().curves.is_empty());
+        assert!(loader.line_indices.is_empty());
+        assert_eq!(loader.indices.len(), 3);
+    }
+}
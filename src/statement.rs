@@ -0,0 +1,159 @@
+use std::io::{BufRead, Lines};
+use crate::*;
+
+/// A whitespace separated token of a statement, along with the 1-based line
+/// and column it starts at for error reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token<'a>{
+    pub text:&'a str,
+    pub line:usize,
+    pub column:usize
+}
+
+impl Token<'_>{
+    pub fn location(&self, path:Option<&str>)->Location{
+        Location::new(path, self.line, self.column)
+    }
+}
+
+/// One logical statement: physical lines ending in `\` are joined with the
+/// line after them, and `#` comments are removed.
+#[derive(Debug, Clone)]
+pub(crate) struct Statement{
+    /// Line the statement starts on.
+    pub line:usize,
+    text:String,
+    /// Where each joined physical line starts in `text`, and its line number.
+    segments:Vec<(usize, usize)>
+}
+
+impl Statement{
+    /// Splits the statement on any whitespace, tabs and stray `\r`s included.
+    pub fn tokens(&self)->Vec<Token<'_>>{
+        let mut tokens = Vec::new();
+        let mut start:Option<usize> = None;
+        for (i, c) in self.text.char_indices(){
+            if c.is_whitespace(){
+                if let Some(s) = start.take(){
+                    tokens.push(self.token(s, i));
+                }
+            }
+            else if start.is_none(){
+                start = Some(i);
+            }
+        }
+        if let Some(s) = start{
+            tokens.push(self.token(s, self.text.len()));
+        }
+        tokens
+    }
+
+    fn token(&self, start:usize, end:usize)->Token<'_>{
+        // Tokens never span segments, continuations are joined with a space
+        let &(segment_start, line) = self.segments.iter().rev()
+            .find(|(segment_start, _)| *segment_start <= start)
+            .unwrap_or(&(0, self.line));
+        Token { text:&self.text[start..end], line, column:start - segment_start + 1 }
+    }
+}
+
+/// Iterates over the statements of an obj or mtl source.
+pub(crate) struct Statements<'p, R>{
+    lines:Lines<R>,
+    line:usize,
+    path:Option<&'p str>
+}
+
+pub(crate) fn statements<R:BufRead>(reader:R, path:Option<&str>)->Statements<'_, R>{
+    Statements { lines:reader.lines(), line:0, path }
+}
+
+impl<R:BufRead> Iterator for Statements<'_, R>{
+    type Item = Result<Statement, ObjError>;
+
+    fn next(&mut self)->Option<Self::Item>{
+        let mut statement = Statement { line:self.line+1, text:String::new(), segments:Vec::new() };
+        loop{
+            let line = match self.lines.next(){
+                Some(line) => line,
+                // A continuation on the last line just ends the statement
+                None if !statement.segments.is_empty() => return Some(Ok(statement)),
+                None => return None
+            };
+            self.line += 1;
+            let line = match line{
+                Ok(line) => line,
+                Err(source) => return Some(Err(ObjError::Io { loc:Location::new(self.path, self.line, 0), source }))
+            };
+            statement.segments.push((statement.text.len(), self.line));
+            let content = strip_comment(&line);
+            match content.trim_end().strip_suffix('\\'){
+                Some(body) => {
+                    statement.text.push_str(body);
+                    statement.text.push(' ');
+                }
+                None => {
+                    statement.text.push_str(content);
+                    return Some(Ok(statement));
+                }
+            }
+        }
+    }
+}
+
+/// Cuts a line off at the first `#` that starts a token, so names like
+/// `mat#2` survive.
+pub(crate) fn strip_comment(line:&str)->&str{
+    let mut after_space = true;
+    for (i, c) in line.char_indices(){
+        if c == '#' && after_space{
+            return &line[..i];
+        }
+        after_space = c.is_whitespace();
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// Each statement's tokens, with their line and column.
+    fn tokens(src:&str)->Vec<Vec<(String, usize, usize)>>{
+        statements(src.as_bytes(), None)
+            .map(|s| s.unwrap().tokens().iter().map(|t| (t.text.to_string(), t.line, t.column)).collect())
+            .collect()
+    }
+
+    fn token(text:&str, line:usize, column:usize)->(String, usize, usize){
+        (text.to_string(), line, column)
+    }
+
+    #[test]
+    fn whitespace(){
+        assert_eq!(tokens("v\t1  2 \r\n\n  f 1 2 3\r\n"), [
+            vec![token("v", 1, 1), token("1", 1, 3), token("2", 1, 6)],
+            vec![],
+            vec![token("f", 3, 3), token("1", 3, 5), token("2", 3, 7), token("3", 3, 9)]
+        ]);
+    }
+
+    #[test]
+    fn continuations(){
+        assert_eq!(tokens("f 1 \\\n  2\\\n3\nv 1 2 3 \\\n"), [
+            vec![token("f", 1, 1), token("1", 1, 3), token("2", 2, 3), token("3", 3, 1)],
+            vec![token("v", 4, 1), token("1", 4, 3), token("2", 4, 5), token("3", 4, 7)]
+        ]);
+    }
+
+    #[test]
+    fn comments(){
+        assert_eq!(tokens("usemtl mat#2 # red\n# f 1 2 3 \\\nv 1#2\n"), [
+            vec![token("usemtl", 1, 1), token("mat#2", 1, 8)],
+            vec![],
+            vec![token("v", 3, 1), token("1#2", 3, 3)]
+        ]);
+        assert_eq!(strip_comment("a b#c #d"), "a b#c ");
+        assert_eq!(strip_comment("#all"), "");
+    }
+}
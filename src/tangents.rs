@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use bevy_mikktspace::Geometry;
+use crate::math::*;
+
+/// One triangle or quad as seen by the tangent generator.
+pub struct TangentFace{
+    pub positions:Vec<V3>,
+    pub tex_coords:Vec<[f64;2]>,
+    pub normals:Vec<V3>
+}
+
+/// Tangents generated for a set of faces: `corners[f][c]` indexes `tangents`
+/// for corner `c` of face `f`. Tangents are `xyz` plus the bitangent sign in
+/// `w`, so `bitangent = w * cross(normal, tangent)`.
+pub struct GeneratedTangents{
+    pub tangents:Vec<[f64;4]>,
+    pub corners:Vec<Vec<usize>>
+}
+
+struct Faces<'a>{
+    faces:&'a [TangentFace],
+    tangents:Vec<Vec<[f32;4]>>
+}
+
+impl Geometry for Faces<'_>{
+    fn num_faces(&self)->usize{
+        self.faces.len()
+    }
+
+    fn num_vertices_of_face(&self, face:usize)->usize{
+        self.faces[face].positions.len()
+    }
+
+    fn position(&self, face:usize, vert:usize)->[f32;3]{
+        self.faces[face].positions[vert].map(|c| c as f32)
+    }
+
+    fn normal(&self, face:usize, vert:usize)->[f32;3]{
+        self.faces[face].normals[vert].map(|c| c as f32)
+    }
+
+    fn tex_coord(&self, face:usize, vert:usize)->[f32;2]{
+        self.faces[face].tex_coords[vert].map(|c| c as f32)
+    }
+
+    fn set_tangent_encoded(&mut self, tangent:[f32;4], face:usize, vert:usize){
+        self.tangents[face][vert] = tangent;
+    }
+}
+
+/// Generates per-corner tangents with MikkTSpace, the tangent space most
+/// normal map bakers use, so baked maps line up. Faces must be triangles or
+/// quads. Corners that end up with equal tangents share an entry.
+pub fn generate_tangents(faces:&[TangentFace])->GeneratedTangents{
+    let mut geometry = Faces { faces, tangents:faces.iter().map(|f| vec![[0.;4]; f.positions.len()]).collect() };
+    // Only fails when there's nothing to work on, every corner is left with
+    // a zero tangent then
+    bevy_mikktspace::generate_tangents(&mut geometry);
+
+    let mut tangents:Vec<[f64;4]> = Vec::new();
+    let mut ids:HashMap<[u32;4], usize> = HashMap::new();
+    let corners = geometry.tangents.iter().map(|face|{
+        face.iter().map(|tangent|{
+            *ids.entry(tangent.map(f32::to_bits)).or_insert_with(|| {
+                tangents.push(tangent.map(f64::from));
+                tangents.len()-1
+            })
+        }).collect()
+    }).collect();
+
+    GeneratedTangents { tangents, corners }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::*;
+
+    fn load(src:&str, triangulation:Triangulation)->ObjLoader{
+        let options = LoadOptions { generate_tangents:true, triangulation, ..Default::default() };
+        ObjLoader::from_str_with(src, &options).unwrap()
+    }
+
+    /// Position, tex coord, normal and tangent of each output vertex.
+    fn vertices(loader:&ObjLoader)->Vec<[f64;12]>{
+        let info = loader.vertex_info();
+        let data = loader.vert_data_f64();
+        let at = |v:&[f64], attribute:Attribute| v[info.offset(attribute).unwrap()..][..attribute.components()].to_vec();
+        data.chunks_exact(info.stride).map(|v|{
+            let attributes = [Attribute::Position, Attribute::TexCoord, Attribute::Normal, Attribute::Tangent];
+            attributes.iter().flat_map(|a| at(v, *a)).collect::<Vec<_>>().try_into().unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn planar_mapping(){
+        let loader = load("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1 4/4/1\n", Triangulation::Fan);
+        for v in vertices(&loader){
+            assert_eq!(v[8..], [1., 0., 0., 1.]);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_split_vertices(){
+        // Two quads sharing the x = 1 edge, the second mirrored in u
+        let loader = load("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 2 0 0\nv 2 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+            f 1/1/1 2/2/1 3/3/1 4/4/1\nf 2/2/1 5/1/1 6/4/1 3/3/1\n", Triangulation::Fan);
+        let vertices = vertices(&loader);
+        assert_eq!(vertices.len(), 8);
+        for v in &vertices{
+            let expected = if v[0] < 1. || (v[0] == 1. && v[11] > 0.) { [1., 0., 0., 1.] } else { [-1., 0., 0., -1.] };
+            assert_eq!(v[8..], expected, "{v:?}");
+        }
+    }
+
+    /// Runs MikkTSpace directly on the output triangles, as a baker given
+    /// the exported mesh would.
+    fn reference(loader:&ObjLoader)->Vec<[f64;4]>{
+        let vertices = vertices(loader);
+        let faces:Vec<TangentFace> = loader.indices.chunks_exact(3).map(|triangle|{
+            let corners = triangle.iter().map(|i| vertices[*i as usize]);
+            TangentFace {
+                positions:corners.clone().map(|v| [v[0], v[1], v[2]]).collect(),
+                tex_coords:corners.clone().map(|v| [v[3], v[4]]).collect(),
+                normals:corners.map(|v| [v[5], v[6], v[7]]).collect()
+            }
+        }).collect();
+        let generated = generate_tangents(&faces);
+        let mut tangents = vec![[0.;4]; vertices.len()];
+        for (triangle, corners) in loader.indices.chunks_exact(3).zip(generated.corners){
+            for (i, corner) in triangle.iter().zip(corners){
+                tangents[*i as usize] = generated.tangents[corner];
+            }
+        }
+        tangents
+    }
+
+    #[test]
+    fn matches_output_triangulation(){
+        // Concave, fanning from the first corner would make triangles
+        // outside the face
+        let src = "v 0 0 0\nv 2 0 0\nv 2 2 0\nv 1 0.5 0\nv 0 2 0\n\
+            vt 0 0\nvt 1 0.1\nvt 0.9 1\nvt 0.6 0.2\nvt 0.1 0.8\nvn 0 0 1\n\
+            f 1/1/1 2/2/1 3/3/1 4/4/1 5/5/1\n";
+        let loader = load(src, Triangulation::EarClipping);
+        let vertices = vertices(&loader);
+        for (v, tangent) in vertices.iter().zip(reference(&loader)){
+            let t = &v[8..];
+            assert!(t.iter().zip(tangent).all(|(a, b)| (a - b).abs() < 1e-6), "{t:?} {tangent:?}");
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::math::*;
+
+/// Fans a polygon with `n` corners out from its first corner. Only correct for
+/// convex polygons.
+pub fn fan(n:usize)->Vec<[usize;3]>{
+    (1..n.saturating_sub(1)).map(|i| [0, i, i+1]).collect()
+}
+
+/// Triangulates a polygon that may be concave by ear clipping. The polygon is
+/// projected onto the plane given by its Newell normal first, so slightly
+/// non-planar faces work too. Returned triangles index into `points` and keep
+/// the polygon's winding.
+pub fn ear_clip(points:&[V3])->Vec<[usize;3]>{
+    let n = points.len();
+    if n < 4{
+        return fan(n);
+    }
+
+    // PROJECT ONTO THE BEST FIT PLANE, with u x v = normal so the polygon's
+    // winding stays counter-clockwise in 2d
+    let normal = normalize(polygon_normal(points));
+    if length(normal) == 0.{
+        return fan(n);
+    }
+    let helper = if normal[0].abs() > 0.9 { [0., 1., 0.] } else { [1., 0., 0.] };
+    let u = normalize(cross(helper, normal));
+    let v = cross(normal, u);
+    let flat:Vec<[f64;2]> = points.iter().map(|p| [dot(*p, u), dot(*p, v)]).collect();
+
+    let mut remaining:Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n-2);
+    while remaining.len() > 3{
+        let count = remaining.len();
+        let ear = (0..count).find(|&i|{
+            let [a, b, c] = [remaining[(i+count-1) % count], remaining[i], remaining[(i+1) % count]];
+            is_ear(&flat, &remaining, a, b, c)
+        });
+        // Degenerate polygons (eg. self intersecting) may have no ears left,
+        // clip the flattest corner so we still make progress
+        let i = ear.unwrap_or_else(|| {
+            (0..count).max_by(|&x, &y|{
+                let corner = |i:usize| turn(&flat, remaining[(i+count-1) % count], remaining[i], remaining[(i+1) % count]);
+                corner(x).total_cmp(&corner(y))
+            }).unwrap_or(0)
+        });
+        triangles.push([remaining[(i+count-1) % count], remaining[i], remaining[(i+1) % count]]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+/// Twice the signed area of the 2d triangle `a b c`, positive when it turns
+/// counter-clockwise.
+fn turn(flat:&[[f64;2]], a:usize, b:usize, c:usize)->f64{
+    let [a, b, c] = [flat[a], flat[b], flat[c]];
+    (b[0]-a[0]) * (c[1]-a[1]) - (b[1]-a[1]) * (c[0]-a[0])
+}
+
+fn is_ear(flat:&[[f64;2]], remaining:&[usize], a:usize, b:usize, c:usize)->bool{
+    if turn(flat, a, b, c) <= 0.{
+        return false;
+    }
+    // No other corner may lie in or on the triangle. Corners sitting exactly
+    // on one of its vertices are ignored, they show up where a hole has been
+    // bridged to the outline.
+    remaining.iter().all(|&p|{
+        if p == a || p == b || p == c{
+            return true;
+        }
+        let point = flat[p];
+        if point == flat[a] || point == flat[b] || point == flat[c]{
+            return true;
+        }
+        turn(flat, a, b, p) < 0. || turn(flat, b, c, p) < 0. || turn(flat, c, a, p) < 0.
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// Checks `triangles` exactly cover `points` with the polygon's winding.
+    fn check_cover(points:&[V3], triangles:&[[usize;3]]){
+        assert_eq!(triangles.len(), points.len() - 2);
+        let normal = polygon_normal(points);
+        let mut area = 0.;
+        for &[a, b, c] in triangles{
+            let doubled = cross(sub(points[b], points[a]), sub(points[c], points[a]));
+            assert!(dot(doubled, normal) > 0., "{a} {b} {c} is wound backwards");
+            area += length(doubled);
+        }
+        assert!((area - length(normal)).abs() < 1e-9, "{area} {}", length(normal));
+    }
+
+    #[test]
+    fn concave(){
+        // An L, fanning from corner 0 would put a triangle outside it
+        let l = [[0., 0., 0.], [2., 0., 0.], [2., 1., 0.], [1., 1., 0.], [1., 2., 0.], [0., 2., 0.]];
+        check_cover(&l, &ear_clip(&l));
+        let mut rotated = l;
+        rotated.rotate_left(3);
+        check_cover(&rotated, &ear_clip(&rotated));
+    }
+
+    #[test]
+    fn winding_and_orientation(){
+        // Clockwise, and tilted out of every axis plane
+        let arrow:Vec<V3> = [[0., 0.], [1., 2.], [2., 0.], [1., 0.5]].iter().rev()
+            .map(|[x, y]| [*x, *y, 0.5 * x + 0.25 * y])
+            .collect();
+        check_cover(&arrow, &ear_clip(&arrow));
+    }
+
+    #[test]
+    fn small_polygons_are_fanned(){
+        let triangle = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]];
+        assert_eq!(ear_clip(&triangle), [[0, 1, 2]]);
+        assert_eq!(fan(5), [[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert!(fan(2).is_empty());
+    }
+}
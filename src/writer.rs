@@ -0,0 +1,379 @@
+use std::{collections::HashMap, fmt::Write as _, io};
+use crate::*;
+
+/// Writes indexed vertex data, like `ObjLoader` produces, back out as obj
+/// text. Equal positions, tex coords and normals are only written once,
+/// compared after rounding to `precision`.
+#[derive(Debug, Clone)]
+pub struct ObjWriter<'a>{
+    pub vert_data:&'a [f32],
+    /// Layout of `vert_data`, only the position, tex coord, normal and color
+    /// attributes are written.
+    pub vertex_info:VertexInfo,
+    pub indices:&'a [u32],
+    /// Corner count of each face in `indices`, when empty every face has
+    /// `face_size` corners.
+    pub face_sizes:&'a [u32],
+    pub face_size:usize,
+    /// Line list written as `l` statements.
+    pub line_indices:&'a [u32],
+    /// Written as `p` statements.
+    pub point_indices:&'a [u32],
+    /// Ranges of `indices` to write under `o` and `g` statements.
+    pub meshes:&'a [Mesh],
+    /// Ranges of `indices` to write under `usemtl` statements.
+    pub material_ranges:&'a [MaterialRange],
+    pub materials:&'a [Material],
+    /// Library to reference with `mtllib`, see `write_mtl`.
+    pub material_lib:Option<String>,
+    /// Digits after the decimal point, trailing zeros are left off.
+    pub precision:usize,
+    /// Added back onto every position, for data rebased with
+    /// `LoadOptions::origin`.
+    pub origin:[f64;3]
+}
+
+impl<'a> ObjWriter<'a>{
+    /// A writer for a plain triangle list.
+    pub fn new(vert_data:&'a [f32], vertex_info:VertexInfo, indices:&'a [u32])->Self{
+        ObjWriter {
+            vert_data,
+            vertex_info,
+            indices,
+            face_sizes:&[],
+            face_size:3,
+            line_indices:&[],
+            point_indices:&[],
+            meshes:&[],
+            material_ranges:&[],
+            materials:&[],
+            material_lib:None,
+            precision:6,
+            origin:[0.;3]
+        }
+    }
+
+    /// A writer for everything a loader holds, apart from the
+    /// `leftover_triangles` of quad output.
+    pub fn from_loader(loader:&'a ObjLoader)->Self{
+        ObjWriter {
+            face_sizes:&loader.face_sizes,
+            face_size:if matches!(loader.primitive(), Primitive::Quads(_)) { 4 } else { 3 },
+            line_indices:&loader.line_indices,
+            point_indices:&loader.point_indices,
+            meshes:loader.meshes(),
+            material_ranges:loader.material_ranges(),
+            materials:loader.materials(),
+            origin:loader.origin(),
+            ..Self::new(&loader.vert_data, loader.vertex_info(), &loader.indices)
+        }
+    }
+
+    /// Writes the obj text to `writer`. Fails with `InvalidInput` if an
+    /// index is out of range for `vert_data`.
+    pub fn write<W:io::Write>(&self, mut writer:W)->io::Result<()>{
+        writer.write_all(self.to_obj_string()?.as_bytes())
+    }
+
+    fn check_indices(&self)->io::Result<()>{
+        let vertex_count = self.vert_data.len() / self.vertex_info.stride.max(1);
+        let mut indices = self.indices.iter().chain(self.line_indices).chain(self.point_indices);
+        match indices.find(|i| **i as usize >= vertex_count){
+            Some(index) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("index {index} is out of range (only {vertex_count} vertices)")
+            )),
+            None => Ok(())
+        }
+    }
+}
+
+/// Deduplicated values of one statement type, numbered in the order they're
+/// first used.
+struct Values{
+    lines:String,
+    seen:HashMap<String, usize>
+}
+
+impl Values{
+    fn new()->Self{
+        Values { lines:String::new(), seen:HashMap::new() }
+    }
+
+    /// The 1-based index of `values`, writing a statement for them if they
+    /// haven't been used before.
+    fn add(&mut self, keyword:&str, values:&str)->usize{
+        let next = self.seen.len() + 1;
+        *self.seen.entry(values.to_string()).or_insert_with(|| {
+            let _ = writeln!(self.lines, "{keyword} {values}");
+            next
+        })
+    }
+}
+
+impl ObjWriter<'_>{
+    /// The obj text, see `write`.
+    pub fn to_obj_string(&self)->io::Result<String>{
+        self.check_indices()?;
+        let info = &self.vertex_info;
+        let stride = info.stride.max(1);
+        let components = |attribute:Attribute| info.offset(attribute).map(|offset| (offset, attribute.components()));
+        let position = components(Attribute::Position);
+        let tex_coord = components(Attribute::TexCoord).or(components(Attribute::TexCoord3));
+        let normal = components(Attribute::Normal);
+        let color = components(Attribute::Color);
+
+        // Values are only written once something uses them, so the
+        // statements are built up before the header is known
+        let mut positions = Values::new();
+        let mut tex_coords = Values::new();
+        let mut normals = Values::new();
+        let mut body = String::new();
+        let mut corner = |index:u32, with_normal:bool|->String{
+            let start = index as usize * stride;
+            let vertex = &self.vert_data[start..start+stride];
+            let format = |(offset, count):(usize, usize)| join(&vertex[offset..offset+count], self.precision);
+            let mut pos = position.map(|(offset, count)|{
+                let values:Vec<f64> = vertex[offset..offset+count].iter().zip(self.origin).map(|(v, o)| f64::from(*v) + o).collect();
+                join(&values, self.precision)
+            }).unwrap_or_default();
+            // Alpha is only written when it isn't opaque
+            if let Some((offset, _)) = color{
+                let count = if vertex[offset+3] == 1. { 3 } else { 4 };
+                pos.push(' ');
+                pos.push_str(&format((offset, count)));
+            }
+            let pos = positions.add("v", &pos);
+            let tex = tex_coord.map(|tex| tex_coords.add("vt", &format(tex)));
+            let norm = normal.filter(|_| with_normal).map(|norm| normals.add("vn", &format(norm)));
+            match (tex, norm){
+                (Some(tex), Some(norm)) => format!("{pos}/{tex}/{norm}"),
+                (None, Some(norm)) => format!("{pos}//{norm}"),
+                (Some(tex), None) => format!("{pos}/{tex}"),
+                (None, None) => pos.to_string()
+            }
+        };
+
+        // FACES, with `o`, `g` and `usemtl` written where their ranges start.
+        // A bare `g` or `usemtl` goes back to no group or material.
+        let (mut object, mut group, mut material) = (None, None, None);
+        let mut start = 0;
+        let mut face = 0;
+        while start < self.indices.len(){
+            for mesh in self.meshes.iter().filter(|mesh| mesh.start == start && mesh.count > 0){
+                if mesh.object.is_some() && mesh.object != object{
+                    let _ = writeln!(body, "o {}", mesh.object.as_deref().unwrap_or_default());
+                    object = mesh.object.clone();
+                }
+                if mesh.group != group{
+                    let _ = writeln!(body, "{}", statement("g", mesh.group.as_deref()));
+                    group = mesh.group.clone();
+                }
+            }
+            let range = self.material_ranges.iter().find(|range| range.start == start && range.count > 0);
+            if let Some(range) = range{
+                let name = range.material.and_then(|m| self.materials.get(m)).map(|m| m.name.as_str());
+                if name != material{
+                    let _ = writeln!(body, "{}", statement("usemtl", name));
+                    material = name;
+                }
+            }
+
+            let size = self.face_sizes.get(face).map_or(self.face_size, |size| *size as usize);
+            let end = (start + size.max(1)).min(self.indices.len());
+            let corners:Vec<String> = self.indices[start..end].iter().map(|i| corner(*i, true)).collect();
+            let _ = writeln!(body, "f {}", corners.join(" "));
+            start = end;
+            face += 1;
+        }
+
+        // LINES AND POINTS, which have no normals
+        for segment in self.line_indices.chunks_exact(2){
+            let _ = writeln!(body, "l {} {}", corner(segment[0], false), corner(segment[1], false));
+        }
+        for &point in self.point_indices{
+            let point = corner(point, false);
+            let _ = writeln!(body, "p {}", point.split('/').next().unwrap_or_default());
+        }
+
+        let mut out = String::new();
+        if let Some(lib) = &self.material_lib{
+            let _ = writeln!(out, "mtllib {lib}");
+        }
+        out.push_str(&positions.lines);
+        out.push_str(&tex_coords.lines);
+        out.push_str(&normals.lines);
+        out.push_str(&body);
+        Ok(out)
+    }
+}
+
+/// `keyword name`, or just the keyword without a name.
+fn statement(keyword:&str, name:Option<&str>)->String{
+    match name{
+        Some(name) => format!("{keyword} {name}"),
+        None => keyword.to_string()
+    }
+}
+
+/// Writes materials as an mtl library. Texture paths are written as they
+/// are, so may need making relative to the library first.
+pub fn write_mtl<W:io::Write>(materials:&[Material], mut writer:W, precision:usize)->io::Result<()>{
+    let mut out = String::new();
+    for (i, material) in materials.iter().enumerate(){
+        if i > 0{
+            out.push('\n');
+        }
+        let _ = writeln!(out, "newmtl {}", material.name);
+        let colors = [("Ka", material.ambient), ("Kd", material.diffuse), ("Ks", material.specular), ("Ke", material.emissive)];
+        for (keyword, color) in colors{
+            if let Some(color) = color{
+                let _ = writeln!(out, "{keyword} {}", join(&color, precision));
+            }
+        }
+        let scalars = [("Ns", material.shininess), ("d", material.dissolve), ("Ni", material.optical_density)];
+        for (keyword, value) in scalars{
+            if let Some(value) = value{
+                let _ = writeln!(out, "{keyword} {}", float(value, precision));
+            }
+        }
+        if let Some(illum) = material.illum{
+            let _ = writeln!(out, "illum {illum}");
+        }
+        let maps = [
+            ("map_Ka", &material.ambient_map),
+            ("map_Kd", &material.diffuse_map),
+            ("map_Ks", &material.specular_map),
+            ("map_Ke", &material.emissive_map),
+            ("map_Ns", &material.shininess_map),
+            ("map_d", &material.dissolve_map),
+            ("map_bump", &material.bump_map),
+            ("disp", &material.displacement_map),
+            ("decal", &material.decal_map),
+            ("refl", &material.reflection_map)
+        ];
+        for (keyword, map) in maps{
+            if let Some(map) = map{
+                let _ = writeln!(out, "{keyword} {}{}", texture_options(&map.options, precision), map.path.display());
+            }
+        }
+    }
+    writer.write_all(out.as_bytes())
+}
+
+/// The `-option` arguments of a texture map that differ from the defaults.
+fn texture_options(options:&TextureOptions, precision:usize)->String{
+    let defaults = TextureOptions::default();
+    let on_off = |on:bool| if on { "on" } else { "off" };
+    let mut out = String::new();
+    if options.blend_u != defaults.blend_u{
+        let _ = write!(out, "-blendu {} ", on_off(options.blend_u));
+    }
+    if options.blend_v != defaults.blend_v{
+        let _ = write!(out, "-blendv {} ", on_off(options.blend_v));
+    }
+    if options.bump_multiplier != defaults.bump_multiplier{
+        let _ = write!(out, "-bm {} ", float(options.bump_multiplier, precision));
+    }
+    if let Some(boost) = options.boost{
+        let _ = write!(out, "-boost {} ", float(boost, precision));
+    }
+    if options.color_correction != defaults.color_correction{
+        let _ = write!(out, "-cc {} ", on_off(options.color_correction));
+    }
+    if options.clamp != defaults.clamp{
+        let _ = write!(out, "-clamp {} ", on_off(options.clamp));
+    }
+    if let Some(channel) = options.channel{
+        let _ = write!(out, "-imfchan {channel} ");
+    }
+    if options.range != defaults.range{
+        let _ = write!(out, "-mm {} ", join(&options.range, precision));
+    }
+    let vectors = [("-o", options.offset, defaults.offset), ("-s", options.scale, defaults.scale), ("-t", options.turbulence, defaults.turbulence)];
+    for (option, value, default) in vectors{
+        if value != default{
+            let _ = write!(out, "{option} {} ", join(&value, precision));
+        }
+    }
+    if let Some(resolution) = options.resolution{
+        let _ = write!(out, "-texres {resolution} ");
+    }
+    if let Some(reflection_type) = &options.reflection_type{
+        let _ = write!(out, "-type {reflection_type} ");
+    }
+    out
+}
+
+/// Formats a number with at most `precision` decimals.
+fn float(value:f64, precision:usize)->String{
+    let mut s = format!("{value:.precision$}");
+    if s.contains('.'){
+        let len = s.trim_end_matches('0').trim_end_matches('.').len();
+        s.truncate(len);
+    }
+    if s == "-0"{
+        s.remove(0);
+    }
+    s
+}
+
+fn join<T:Copy + Into<f64>>(values:&[T], precision:usize)->String{
+    values.iter().map(|v| float((*v).into(), precision)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn load(src:&str, options:&LoadOptions)->ObjLoader{
+        ObjLoader::from_str_with(src, &LoadOptions { load_materials:false, ..options.clone() }).unwrap()
+    }
+
+    #[test]
+    fn round_trip(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n\
+            f 1/1/1 2/2/1 3/2/1 4/1/1\nl 1 3\np 2\n";
+        let options = LoadOptions { primitive:Primitive::Polygons, ..Default::default() };
+        let loader = load(src, &options);
+        let text = ObjWriter::from_loader(&loader).to_obj_string().unwrap();
+        assert_eq!(text, "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n\
+            f 1/1/1 2/2/1 3/2/1 4/1/1\nl 1/1 3/1\np 2\n");
+        let reloaded = load(&text, &options);
+        assert_eq!(reloaded.vert_data, loader.vert_data);
+        assert_eq!(reloaded.indices, loader.indices);
+    }
+
+    #[test]
+    fn origin_added_back(){
+        let src = "v 1000000.5 0 0\nv 1000001.5 0 0\nv 1000001.5 1 0\nf 1 2 3\n";
+        let options = LoadOptions { origin:Some(Origin::Computed), ..Default::default() };
+        let loader = load(src, &options);
+        assert_eq!(loader.origin(), [1000001., 0.5, 0.]);
+        let text = ObjWriter::from_loader(&loader).to_obj_string().unwrap();
+        assert!(text.starts_with("v 1000000.5 0 0\nv 1000001.5 0 0\nv 1000001.5 1 0\n"), "{text}");
+    }
+
+    #[test]
+    fn out_of_range_indices(){
+        let vert_data = [0., 0., 0.];
+        let info = VertexInfo::new(1, &[Attribute::Position]);
+        let writer = ObjWriter::new(&vert_data, info, &[0, 1, 7]);
+        let err = writer.write(Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(writer.to_obj_string().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn groups_and_materials_reset(){
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\ng a\nusemtl red\nf 1 2 3\ng\nusemtl\nf 3 2 1\n";
+        let options = LoadOptions { split:SplitBy { groups:true, materials:true, ..Default::default() }, ..Default::default() };
+        let loader = load(src, &options);
+        let text = ObjWriter::from_loader(&loader).to_obj_string().unwrap();
+        assert!(text.ends_with("g a\nusemtl red\nf 1 2 3\ng\nusemtl\nf 3 2 1\n"), "{text}");
+
+        let reloaded = load(&text, &options);
+        let groups:Vec<_> = reloaded.meshes().iter().map(|m| (m.group.clone(), m.material)).collect();
+        assert_eq!(groups, [(Some("a".to_string()), Some(0)), (None, None)]);
+    }
+}